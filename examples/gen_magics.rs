@@ -0,0 +1,21 @@
+use rejectchess::dirs::{BISHOP_DIRS, ROOK_DIRS};
+use rejectchess::magic::find_magics;
+
+fn main() {
+    print_table(
+        "BISHOP_MAGICS",
+        &find_magics(&BISHOP_DIRS, 0x2545_f491_4f6c_dd1d),
+    );
+    print_table(
+        "ROOK_MAGICS",
+        &find_magics(&ROOK_DIRS, 0x9e37_79b9_7f4a_7c15),
+    );
+}
+
+fn print_table(name: &str, magics: &[u64]) {
+    println!("const {}: [u64; 64] = [", name);
+    for magic in magics {
+        println!("    0x{:016x},", magic);
+    }
+    println!("];");
+}
@@ -0,0 +1,245 @@
+use crate::board::{Color, Square};
+use crate::dirs::{KING_DIRS, KNIGHT_DIRS, QUEEN_DIRS};
+use crate::magic;
+
+// Bit `rank * 8 + file` is set for every square in the set, so a1 is bit 0
+// and h8 is bit 63.
+pub type Bitboard = u64;
+
+pub const DARK_SQUARES: Bitboard = 0xaa55_aa55_aa55_aa55;
+pub const LIGHT_SQUARES: Bitboard = !DARK_SQUARES;
+pub const FILE_A: Bitboard = 0x0101_0101_0101_0101;
+pub const FILE_H: Bitboard = FILE_A << 7;
+
+pub const KNIGHT_ATTACKS: [Bitboard; 64] = step_attacks(&KNIGHT_DIRS);
+pub const KING_ATTACKS: [Bitboard; 64] = step_attacks(&KING_DIRS);
+// Indexed by the color of the pawn doing the attacking.
+pub const PAWN_ATTACKS: [[Bitboard; 64]; 2] = [
+    step_attacks(&[(-1, 1), (1, 1)]),
+    step_attacks(&[(-1, -1), (1, -1)]),
+];
+
+// Squares strictly between two squares on a shared rank, file or diagonal;
+// empty when the squares are not aligned.
+pub static BETWEEN: [[Bitboard; 64]; 64] = between_table();
+
+pub const fn square_index(sq: Square) -> usize {
+    sq.1 as usize * 8 + sq.0 as usize
+}
+
+pub const fn index_square(index: usize) -> Square {
+    ((index % 8) as u8, (index / 8) as u8)
+}
+
+pub const fn square_bit(sq: Square) -> Bitboard {
+    1 << square_index(sq)
+}
+
+pub fn squares(bb: Bitboard) -> Squares {
+    Squares(bb)
+}
+
+pub struct Squares(Bitboard);
+
+impl Iterator for Squares {
+    type Item = Square;
+
+    fn next(&mut self) -> Option<Square> {
+        if self.0 == 0 {
+            return None;
+        }
+        let index = self.0.trailing_zeros() as usize;
+        self.0 &= self.0 - 1;
+        Some(index_square(index))
+    }
+}
+
+pub fn pawn_attacks(color: Color, sq: Square) -> Bitboard {
+    PAWN_ATTACKS[color as usize][square_index(sq)]
+}
+
+// Every square some pawn in `pawns` attacks.
+pub fn pawn_set_attacks(color: Color, pawns: Bitboard) -> Bitboard {
+    let west = pawns & !FILE_A;
+    let east = pawns & !FILE_H;
+    match color {
+        Color::White => west << 7 | east << 9,
+        Color::Black => west >> 9 | east >> 7,
+    }
+}
+
+// Every square a king standing on any square of `kings` could step to.
+pub fn king_set_attacks(kings: Bitboard) -> Bitboard {
+    let row = kings | (kings & !FILE_A) >> 1 | (kings & !FILE_H) << 1;
+    (row | row << 8 | row >> 8) & !kings
+}
+
+pub fn knight_attacks(sq: Square) -> Bitboard {
+    KNIGHT_ATTACKS[square_index(sq)]
+}
+
+pub fn king_attacks(sq: Square) -> Bitboard {
+    KING_ATTACKS[square_index(sq)]
+}
+
+pub fn bishop_attacks(sq: Square, occupied: Bitboard) -> Bitboard {
+    magic::bishop_attacks(square_index(sq), occupied)
+}
+
+pub fn rook_attacks(sq: Square, occupied: Bitboard) -> Bitboard {
+    magic::rook_attacks(square_index(sq), occupied)
+}
+
+pub fn queen_attacks(sq: Square, occupied: Bitboard) -> Bitboard {
+    bishop_attacks(sq, occupied) | rook_attacks(sq, occupied)
+}
+
+pub fn between(a: Square, b: Square) -> Bitboard {
+    BETWEEN[square_index(a)][square_index(b)]
+}
+
+// Walks each ray until it leaves the board or hits an occupied square, which
+// is included in the result. The magic tables are built from this.
+pub const fn ray_attacks(sq: Square, occupied: Bitboard, dirs: &[(i8, i8)]) -> Bitboard {
+    let mut attacks = 0;
+    let mut i = 0;
+    while i < dirs.len() {
+        let (df, dr) = dirs[i];
+        let mut file = sq.0 as i8 + df;
+        let mut rank = sq.1 as i8 + dr;
+        while file >= 0 && file < 8 && rank >= 0 && rank < 8 {
+            let bit = square_bit((file as u8, rank as u8));
+            attacks |= bit;
+            if occupied & bit != 0 {
+                break;
+            }
+            file += df;
+            rank += dr;
+        }
+        i += 1;
+    }
+    attacks
+}
+
+const fn step_attacks(dirs: &[(i8, i8)]) -> [Bitboard; 64] {
+    let mut table = [0; 64];
+    let mut index = 0;
+    while index < 64 {
+        let sq = index_square(index);
+        let mut i = 0;
+        while i < dirs.len() {
+            let file = sq.0 as i8 + dirs[i].0;
+            let rank = sq.1 as i8 + dirs[i].1;
+            if file >= 0 && file < 8 && rank >= 0 && rank < 8 {
+                table[index] |= square_bit((file as u8, rank as u8));
+            }
+            i += 1;
+        }
+        index += 1;
+    }
+    table
+}
+
+const fn between_table() -> [[Bitboard; 64]; 64] {
+    let mut table = [[0; 64]; 64];
+    let mut index = 0;
+    while index < 64 {
+        let sq = index_square(index);
+        let mut i = 0;
+        while i < QUEEN_DIRS.len() {
+            let (df, dr) = QUEEN_DIRS[i];
+            let mut file = sq.0 as i8 + df;
+            let mut rank = sq.1 as i8 + dr;
+            let mut passed = 0;
+            while file >= 0 && file < 8 && rank >= 0 && rank < 8 {
+                let target = square_index((file as u8, rank as u8));
+                table[index][target] = passed;
+                passed |= 1 << target;
+                file += df;
+                rank += dr;
+            }
+            i += 1;
+        }
+        index += 1;
+    }
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dirs::{BISHOP_DIRS, ROOK_DIRS};
+
+    #[test]
+    fn step_tables_match_board_geometry() {
+        assert_eq!(knight_attacks((0, 0)).count_ones(), 2);
+        assert_eq!(knight_attacks((4, 4)).count_ones(), 8);
+        assert_eq!(king_attacks((0, 0)).count_ones(), 3);
+        assert_eq!(king_attacks((4, 4)).count_ones(), 8);
+        assert_eq!(
+            pawn_attacks(Color::White, (4, 1)),
+            square_bit((3, 2)) | square_bit((5, 2))
+        );
+        assert_eq!(pawn_attacks(Color::Black, (0, 6)), square_bit((1, 5)));
+        assert!(DARK_SQUARES & square_bit((0, 0)) != 0);
+        assert!(LIGHT_SQUARES & square_bit((7, 0)) != 0);
+    }
+
+    #[test]
+    fn set_attacks_match_the_step_tables() {
+        for index in 0..64 {
+            let sq = index_square(index);
+            let bit = square_bit(sq);
+            assert_eq!(king_set_attacks(bit), king_attacks(sq));
+            for color in [Color::White, Color::Black] {
+                assert_eq!(pawn_set_attacks(color, bit), pawn_attacks(color, sq));
+            }
+        }
+        let pawns = square_bit((0, 3)) | square_bit((7, 3));
+        assert_eq!(
+            pawn_set_attacks(Color::White, pawns),
+            square_bit((1, 4)) | square_bit((6, 4))
+        );
+    }
+
+    #[test]
+    fn slider_attacks_stop_at_blockers() {
+        let occupied = square_bit((3, 5)) | square_bit((6, 3));
+        let attacks = rook_attacks((3, 3), occupied);
+        assert!(attacks & square_bit((3, 5)) != 0);
+        assert!(attacks & square_bit((3, 6)) == 0);
+        assert!(attacks & square_bit((6, 3)) != 0);
+        assert!(attacks & square_bit((7, 3)) == 0);
+        assert_eq!(attacks.count_ones(), 11);
+        assert_eq!(bishop_attacks((0, 0), 0).count_ones(), 7);
+        assert_eq!(
+            rook_attacks((3, 3), occupied),
+            ray_attacks((3, 3), occupied, &ROOK_DIRS)
+        );
+        assert_eq!(
+            bishop_attacks((3, 3), occupied),
+            ray_attacks((3, 3), occupied, &BISHOP_DIRS)
+        );
+    }
+
+    #[test]
+    fn between_covers_only_aligned_squares() {
+        assert_eq!(
+            between((0, 0), (3, 3)),
+            square_bit((1, 1)) | square_bit((2, 2))
+        );
+        assert_eq!(between((4, 0), (4, 7)).count_ones(), 6);
+        assert_eq!(between((4, 0), (4, 7)), between((4, 7), (4, 0)));
+        assert_eq!(between((0, 0), (1, 2)), 0);
+        assert_eq!(between((3, 3), (4, 4)), 0);
+    }
+
+    #[test]
+    fn squares_iterates_set_bits_in_order() {
+        let bb = square_bit((7, 7)) | square_bit((0, 0)) | square_bit((2, 1));
+        assert_eq!(
+            squares(bb).collect::<Vec<_>>(),
+            vec![(0, 0), (2, 1), (7, 7)]
+        );
+    }
+}
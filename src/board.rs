@@ -1,3 +1,8 @@
+use crate::bitboard::{
+    Bitboard, bishop_attacks, king_attacks, knight_attacks, pawn_attacks, rook_attacks, square_bit,
+    square_index,
+};
+
 #[derive(Copy, Clone, Eq, PartialEq, Debug)]
 pub enum Color {
     White,
@@ -30,16 +35,81 @@ pub struct Piece {
 }
 
 pub type Square = (u8, u8); // file, rank
-pub type Board = [[Option<Piece>; 8]; 8];
+
+// Mailbox for "what is on this square" plus bitboards for "where are these
+// pieces"; `set_piece` keeps the two in sync.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct Board {
+    mailbox: [Option<Piece>; 64],
+    kinds: [Bitboard; 6],
+    colors: [Bitboard; 2],
+}
+
+impl Board {
+    pub fn empty() -> Self {
+        Self {
+            mailbox: [None; 64],
+            kinds: [0; 6],
+            colors: [0; 2],
+        }
+    }
+
+    pub fn piece_at(&self, sq: Square) -> Option<Piece> {
+        self.mailbox[square_index(sq)]
+    }
+
+    pub fn set_piece(&mut self, sq: Square, piece: Option<Piece>) {
+        let index = square_index(sq);
+        let bit = square_bit(sq);
+        if let Some(old) = self.mailbox[index] {
+            self.kinds[old.kind as usize] &= !bit;
+            self.colors[old.color as usize] &= !bit;
+        }
+        if let Some(new) = piece {
+            self.kinds[new.kind as usize] |= bit;
+            self.colors[new.color as usize] |= bit;
+        }
+        self.mailbox[index] = piece;
+    }
+
+    pub fn occupied(&self) -> Bitboard {
+        self.colors[0] | self.colors[1]
+    }
+
+    pub fn pieces_of_color(&self, color: Color) -> Bitboard {
+        self.colors[color as usize]
+    }
+
+    pub fn pieces_of_kind(&self, kind: PieceKind) -> Bitboard {
+        self.kinds[kind as usize]
+    }
+
+    // Pieces of `by_color` that attack `sq` when the board holds `occupied`,
+    // which may differ from the real occupancy to look through movers.
+    pub fn attackers(&self, sq: Square, by_color: Color, occupied: Bitboard) -> Bitboard {
+        let queens = self.pieces(by_color, PieceKind::Queen);
+        let diagonal = self.pieces(by_color, PieceKind::Bishop) | queens;
+        let orthogonal = self.pieces(by_color, PieceKind::Rook) | queens;
+        (pawn_attacks(by_color.opposite(), sq) & self.pieces(by_color, PieceKind::Pawn))
+            | (knight_attacks(sq) & self.pieces(by_color, PieceKind::Knight))
+            | (king_attacks(sq) & self.pieces(by_color, PieceKind::King))
+            | (bishop_attacks(sq, occupied) & diagonal)
+            | (rook_attacks(sq, occupied) & orthogonal)
+    }
+
+    pub fn pieces(&self, color: Color, kind: PieceKind) -> Bitboard {
+        self.colors[color as usize] & self.kinds[kind as usize]
+    }
+}
 
 pub fn in_bounds(file: i8, rank: i8) -> bool {
-    file >= 0 && file < 8 && rank >= 0 && rank < 8
+    (0..8).contains(&file) && (0..8).contains(&rank)
 }
 
 pub fn piece_at(board: &Board, sq: Square) -> Option<Piece> {
-    board[sq.1 as usize][sq.0 as usize]
+    board.piece_at(sq)
 }
 
 pub fn set_piece(board: &mut Board, sq: Square, piece: Option<Piece>) {
-    board[sq.1 as usize][sq.0 as usize] = piece;
+    board.set_piece(sq, piece);
 }
@@ -1,37 +1,167 @@
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
 use rayon::prelude::*;
 
-use crate::board::{PieceKind, piece_at};
+use crate::board::{Color, PieceKind};
 use crate::game::{Game, IllegalMove};
 use crate::movegen;
-use crate::moves::{Move, MoveKind};
+use crate::moves::{Move, MoveList};
 use crate::rules;
-use crate::state::GameState;
+use crate::state::{FenError, GameState};
+use crate::timeman::{DEFAULT_MOVE_OVERHEAD, TimeBudget};
+use crate::tt::{Bound, TranspositionTable, TtEntry};
 
+// Mate scores count down by one per ply from the root, so shorter mates
+// score higher.
 const MATE_SCORE: i32 = 1_000_000;
 const INF: i32 = 1_000_000_000;
-const SEARCH_DEPTH: u8 = 7;
+// Depth searched when `go` comes without any limit at all.
+const DEFAULT_DEPTH: u8 = 7;
+const MAX_DEPTH: u8 = 64;
+// Deepest ply any search reaches, quiescence included; quiescence stops
+// here. Every mate score lies within this many points of `MATE_SCORE`.
+const MAX_PLY: u8 = u8::MAX;
+// How many nodes pass between clock reads.
+const CLOCK_CHECK_INTERVAL: u64 = 1024;
+// Slack, in pawns, for positional gains a capture might bring beyond the
+// material it wins before quiescence search gives up on it.
+const DELTA_MARGIN: i32 = 2;
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default)]
+pub struct SearchLimits {
+    pub wtime: Option<Duration>,
+    pub btime: Option<Duration>,
+    pub winc: Option<Duration>,
+    pub binc: Option<Duration>,
+    pub movestogo: Option<u32>,
+    pub movetime: Option<Duration>,
+    pub depth: Option<u8>,
+    pub nodes: Option<u64>,
+    // Look for a mate in this many moves.
+    pub mate: Option<u32>,
+    // Ignore the clock and search until told to stop.
+    pub infinite: bool,
+    // Search on the opponent's time; the clock only applies after ponderhit.
+    pub ponder: bool,
+}
+
+// Lets another thread steer a running search: `stop` ends it with the best
+// move found so far and `ponderhit` turns a ponder search into a normal one.
+#[derive(Debug, Default)]
+pub struct SearchControl {
+    stop: AtomicBool,
+    pondering: AtomicBool,
+    ponderhit_at: Mutex<Option<Instant>>,
+}
+
+impl SearchControl {
+    pub fn new(ponder: bool) -> Self {
+        Self {
+            pondering: AtomicBool::new(ponder),
+            ..Self::default()
+        }
+    }
+
+    pub fn stop(&self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_stopped(&self) -> bool {
+        self.stop.load(Ordering::Relaxed)
+    }
+
+    pub fn ponderhit(&self) {
+        *self.ponderhit_at.lock().unwrap() = Some(Instant::now());
+        self.pondering.store(false, Ordering::Relaxed);
+    }
+
+    pub fn is_pondering(&self) -> bool {
+        self.pondering.load(Ordering::Relaxed)
+    }
+
+    // When our clock started running: at ponderhit for a ponder search,
+    // otherwise when the search did. `None` while still pondering.
+    fn clock_start(&self, search_start: Instant) -> Option<Instant> {
+        if self.is_pondering() {
+            return None;
+        }
+        Some(self.ponderhit_at.lock().unwrap().unwrap_or(search_start))
+    }
+}
 
+// The outcome of one completed iteration.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct SearchInfo {
+    pub best_move: Move,
+    pub score: i32,
+    pub depth: u8,
+    pub nodes: u64,
+    pub elapsed: Duration,
+    // Permille of the transposition table in use, for UCI `hashfull`.
+    pub hashfull: u16,
+}
+
+impl SearchInfo {
+    // Moves until mate when the score is a forced mate: positive when the
+    // side to move delivers it, negative when it is on the receiving end.
+    pub fn mate(&self) -> Option<i32> {
+        if !is_mate_score(self.score) {
+            return None;
+        }
+        let distance = MATE_SCORE - self.score.abs();
+        let moves = (distance + 1) / 2;
+        Some(if self.score > 0 { moves } else { -moves })
+    }
+}
+
+// Clones share the transposition table, so a search on a clone leaves its
+// results for the next one.
+#[derive(Clone)]
 pub struct Engine {
     game: Game,
+    move_overhead: Duration,
+    tt: Arc<TranspositionTable>,
+}
+
+impl Default for Engine {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Engine {
     pub fn new() -> Self {
-        Self { game: Game::new() }
+        Self {
+            game: Game::new(),
+            move_overhead: DEFAULT_MOVE_OVERHEAD,
+            tt: Arc::default(),
+        }
     }
 
     pub fn reset(&mut self) {
         self.game = Game::new();
     }
 
-    pub fn set_fen(&mut self, fen: &str) -> bool {
-        match Game::from_fen(fen) {
-            Some(game) => {
-                self.game = game;
-                true
-            }
-            None => false,
-        }
+    pub fn set_fen(&mut self, fen: &str) -> Result<(), FenError> {
+        self.game = Game::from_fen(fen)?;
+        Ok(())
+    }
+
+    pub fn set_move_overhead(&mut self, overhead: Duration) {
+        self.move_overhead = overhead;
+    }
+
+    // Replaces the transposition table with an empty one of this many
+    // megabytes. Clones made earlier keep the old table.
+    pub fn set_hash_size(&mut self, megabytes: usize) {
+        self.tt = Arc::new(TranspositionTable::new(megabytes));
+    }
+
+    pub fn clear_hash(&self) {
+        self.tt.clear();
     }
 
     pub fn legal_moves(&self) -> Vec<Move> {
@@ -45,89 +175,457 @@ impl Engine {
         Ok(())
     }
 
-    pub fn go(&self) -> Option<(Move, i32)> {
-        let moves = ordered_candidates(&self.game.state);
+    // Iterative deepening: searches depth 1, 2, ... until the depth limit,
+    // the soft time limit or the hard time limit, calling `on_iteration`
+    // after each completed depth. Returns the result of the last completed
+    // iteration; an iteration cut short by a limit or by `control.stop` is
+    // thrown away. Infinite and ponder searches that run out of depth wait
+    // for `stop` (or `ponderhit`) before returning, as UCI requires.
+    pub fn go(
+        &self,
+        limits: &SearchLimits,
+        control: &SearchControl,
+        mut on_iteration: impl FnMut(&SearchInfo),
+    ) -> Option<SearchInfo> {
+        let start = Instant::now();
+        let mut root = self.game.state.clone();
+        let tt_move = self
+            .tt
+            .probe(root.zobrist)
+            .and_then(|entry| entry.best_move);
+        let mut moves = ordered_candidates(&root, tt_move);
         if moves.is_empty() {
+            wait_for_stop(limits, control);
             return None;
         }
 
-        let depth = SEARCH_DEPTH.saturating_sub(1);
-        let mut best_move = None;
-        let mut best_score = i32::MIN;
-        let mut first_index = None;
+        let budget = if limits.infinite {
+            None
+        } else {
+            self.budget(limits)
+        };
+        let unbounded =
+            budget.is_some() || limits.infinite || limits.nodes.is_some() || limits.mate.is_some();
+        let mut max_depth = match limits.depth {
+            Some(depth) => depth.clamp(1, MAX_DEPTH),
+            None if unbounded => MAX_DEPTH,
+            None => DEFAULT_DEPTH,
+        };
+        if let Some(mate) = limits.mate {
+            let plies = mate.saturating_mul(2).clamp(1, MAX_DEPTH as u32);
+            max_depth = max_depth.min(plies as u8);
+        }
+        let abort = AtomicBool::new(false);
+        let counted = AtomicU64::new(0);
+        let mut nodes = 0;
+        let mut best: Option<SearchInfo> = None;
+        self.tt.new_search();
 
-        for (idx, mv) in moves.iter().copied().enumerate() {
-            if let Some(next) = rules::try_apply_legal(&self.game.state, mv) {
-                best_score = -search_ab(&next, depth, -INF, INF);
-                best_move = Some(mv);
-                first_index = Some(idx);
+        for depth in 1..=max_depth {
+            // The first iteration is cheap and always runs to completion so
+            // there is a move to play however tight the limits.
+            let limited = depth > 1;
+            let clock = Clock {
+                control: Some(control).filter(|_| limited),
+                start,
+                hard_limit: budget.filter(|_| limited).map(|budget| budget.hard),
+                node_limit: limits.nodes.filter(|_| limited),
+                nodes: &counted,
+                abort: &abort,
+            };
+            let Some((best_move, score, searched)) =
+                search_root(&mut root, &moves, depth, &clock, &self.tt)
+            else {
+                break;
+            };
+            nodes += searched;
+            if clock.stopped() {
                 break;
             }
-        }
 
-        let Some(start) = first_index else {
-            return None;
-        };
+            let info = SearchInfo {
+                best_move,
+                score,
+                depth,
+                nodes,
+                elapsed: start.elapsed(),
+                hashfull: self.tt.hashfull(),
+            };
+            on_iteration(&info);
+            best = Some(info);
 
-        if start + 1 < moves.len() {
-            let alpha0 = best_score;
-            if let Some((score, mv)) = moves[start + 1..]
-                .par_iter()
-                .filter_map(|&mv| {
-                    let next = rules::try_apply_legal(&self.game.state, mv)?;
-                    let score = -search_ab(&next, depth, -INF, -alpha0);
-                    Some((score, mv))
-                })
-                .max_by_key(|(score, _)| *score)
+            moves.sort_by_key(|mv| mv != best_move);
+            if info.mate().is_some() {
+                break;
+            }
+            if let Some(limit) = limits.nodes
+                && nodes >= limit
             {
-                if score > best_score {
-                    best_score = score;
-                    best_move = Some(mv);
-                }
+                break;
+            }
+            if let Some(budget) = budget
+                && let Some(clock_start) = control.clock_start(start)
+                && clock_start.elapsed() >= budget.soft
+            {
+                break;
             }
         }
 
-        best_move.map(|mv| (mv, best_score))
+        wait_for_stop(limits, control);
+        best
+    }
+
+    fn budget(&self, limits: &SearchLimits) -> Option<TimeBudget> {
+        if let Some(movetime) = limits.movetime {
+            return Some(TimeBudget::for_move_time(movetime, self.move_overhead));
+        }
+        let (remaining, increment) = match self.game.state.side_to_move {
+            Color::White => (limits.wtime, limits.winc),
+            Color::Black => (limits.btime, limits.binc),
+        };
+        remaining.map(|remaining| {
+            TimeBudget::for_clock(
+                remaining,
+                increment.unwrap_or_default(),
+                limits.movestogo,
+                self.move_overhead,
+            )
+        })
+    }
+
+    pub fn divide(&self, depth: u8) -> Vec<(Move, u64)> {
+        rules::divide(&self.game.state, depth)
     }
+}
 
-    pub fn search_depth(&self) -> u8 {
-        SEARCH_DEPTH
+// UCI forbids `bestmove` before `stop` in an infinite search, and before
+// `stop` or `ponderhit` while pondering, however early the search ends.
+fn wait_for_stop(limits: &SearchLimits, control: &SearchControl) {
+    while !control.is_stopped() && (limits.infinite || control.is_pondering()) {
+        thread::sleep(Duration::from_millis(1));
     }
 }
 
-fn search_ab(state: &GameState, depth: u8, mut alpha: i32, beta: i32) -> i32 {
-    let moves = ordered_candidates(state);
-    if depth == 0 {
-        return eval_material_for_side_to_move(state);
+// Shared by every thread working on one iteration. Once the hard limit or
+// the node limit passes, the first thread to notice raises `abort` and the
+// rest unwind. `nodes` is topped up in batches and with the remainder when
+// a searcher finishes, so an iteration that completes never exceeds the node
+// limit. Without `control` the iteration cannot be stopped from outside.
+struct Clock<'a> {
+    control: Option<&'a SearchControl>,
+    start: Instant,
+    hard_limit: Option<Duration>,
+    node_limit: Option<u64>,
+    nodes: &'a AtomicU64,
+    abort: &'a AtomicBool,
+}
+
+impl Clock<'_> {
+    fn stopped(&self) -> bool {
+        self.abort.load(Ordering::Relaxed) || self.control.is_some_and(SearchControl::is_stopped)
     }
 
-    let mut best = i32::MIN;
-    let mut found_legal = false;
-    for mv in moves {
-        let Some(next) = rules::try_apply_legal(state, mv) else {
-            continue;
-        };
-        found_legal = true;
-        let score = -search_ab(&next, depth - 1, -beta, -alpha);
-        if score > best {
-            best = score;
+    fn check(&self, searched: u64) -> bool {
+        if self.stopped() {
+            return true;
         }
-        if score > alpha {
-            alpha = score;
+        let total = self.nodes.fetch_add(searched, Ordering::Relaxed) + searched;
+        let out_of_nodes = self.node_limit.is_some_and(|limit| total >= limit);
+        let out_of_time = self.hard_limit.is_some_and(|limit| {
+            self.control
+                .and_then(|control| control.clock_start(self.start))
+                .is_some_and(|clock_start| clock_start.elapsed() >= limit)
+        });
+        if out_of_nodes || out_of_time {
+            self.abort.store(true, Ordering::Relaxed);
+            return true;
         }
-        if alpha >= beta {
+        false
+    }
+}
+
+// One root iteration: the first legal move is searched with a full window to
+// set a bound, the rest in parallel against it. Returns the best move, its
+// score and the node count.
+fn search_root(
+    root: &mut GameState,
+    moves: &MoveList,
+    depth: u8,
+    clock: &Clock,
+    tt: &TranspositionTable,
+) -> Option<(Move, i32, u64)> {
+    let child_depth = depth - 1;
+    let mut searcher = Searcher {
+        clock,
+        tt,
+        nodes: 0,
+    };
+    let mut best = None;
+    let mut first_index = None;
+
+    for (idx, mv) in moves.iter().enumerate() {
+        if let Some(record) = rules::make_move(root, mv) {
+            let score = -searcher.search_ab(root, child_depth, 1, -INF, INF);
+            rules::unmake_move(root, &record);
+            searcher.finish();
+            best = Some((mv, score));
+            first_index = Some(idx);
             break;
         }
     }
-    if !found_legal {
-        return terminal_score(state);
+
+    let (mut best_move, mut best_score) = best?;
+    let start = first_index?;
+    let mut nodes = searcher.nodes;
+
+    if start + 1 < moves.len() {
+        let alpha0 = best_score;
+        let results: Vec<(i32, Move, u64)> = moves.as_slice()[start + 1..]
+            .par_iter()
+            .filter_map(|&packed| {
+                let mv = Move::from(packed);
+                let mut state = root.clone();
+                rules::make_move(&mut state, mv)?;
+                let mut searcher = Searcher {
+                    clock,
+                    tt,
+                    nodes: 0,
+                };
+                let score = -searcher.search_ab(&mut state, child_depth, 1, -INF, -alpha0);
+                searcher.finish();
+                Some((score, mv, searcher.nodes))
+            })
+            .collect();
+        nodes += results.iter().map(|(_, _, nodes)| nodes).sum::<u64>();
+        if let Some(&(score, mv, _)) = results.iter().max_by_key(|(score, _, _)| *score)
+            && score > best_score
+        {
+            best_score = score;
+            best_move = mv;
+        }
+    }
+
+    Some((best_move, best_score, nodes))
+}
+
+struct Searcher<'a> {
+    clock: &'a Clock<'a>,
+    tt: &'a TranspositionTable,
+    nodes: u64,
+}
+
+impl Searcher<'_> {
+    // Hands the clock the nodes searched since its last batch.
+    fn finish(&self) {
+        self.clock.check(self.nodes % CLOCK_CHECK_INTERVAL);
+    }
+
+    fn search_ab(
+        &mut self,
+        state: &mut GameState,
+        depth: u8,
+        ply: u8,
+        mut alpha: i32,
+        beta: i32,
+    ) -> i32 {
+        self.nodes += 1;
+        if self.nodes.is_multiple_of(CLOCK_CHECK_INTERVAL) && self.clock.check(CLOCK_CHECK_INTERVAL)
+            || self.clock.stopped()
+        {
+            return 0;
+        }
+        if rules::is_dead_position(state) {
+            return 0;
+        }
+        if depth == 0 {
+            return self.quiesce(state, 0, ply, alpha, beta);
+        }
+        let key = state.zobrist;
+        let entry = self.tt.probe(key);
+        if let Some(entry) = entry
+            && entry.depth >= depth
+        {
+            let score = score_from_tt(entry.score, ply);
+            let cutoff = match entry.bound {
+                Bound::Exact => true,
+                Bound::Lower => score >= beta,
+                Bound::Upper => score <= alpha,
+            };
+            if cutoff {
+                return score;
+            }
+        }
+        let moves = ordered_candidates(state, entry.and_then(|entry| entry.best_move));
+        let alpha_orig = alpha;
+
+        let mut best = i32::MIN;
+        let mut best_move = None;
+        let mut found_legal = false;
+        for mv in &moves {
+            let Some(record) = rules::make_move(state, mv) else {
+                continue;
+            };
+            found_legal = true;
+            let score = -self.search_ab(state, depth - 1, ply + 1, -beta, -alpha);
+            rules::unmake_move(state, &record);
+            if score > best {
+                best = score;
+                best_move = Some(mv);
+            }
+            if score > alpha {
+                alpha = score;
+            }
+            if alpha >= beta {
+                break;
+            }
+        }
+        if !found_legal {
+            return terminal_score(state, ply);
+        }
+        // An aborted search returns made-up scores; keep them out of the table.
+        if self.clock.stopped() {
+            return best;
+        }
+        let bound = if best >= beta {
+            Bound::Lower
+        } else if best > alpha_orig {
+            Bound::Exact
+        } else {
+            Bound::Upper
+        };
+        self.tt.store(
+            key,
+            TtEntry {
+                // When every move failed low none of them is known to be best.
+                best_move: best_move.filter(|_| bound != Bound::Upper),
+                score: score_to_tt(best, ply),
+                depth,
+                bound,
+            },
+        );
+        best
+    }
+
+    // Searches captures and promotions until the position is quiet, so the
+    // horizon never falls in the middle of an exchange. The side to move may
+    // stand pat on the static evaluation instead of capturing, except in
+    // check, where every evasion is searched. Quiet checks are tried at the
+    // first ply only.
+    fn quiesce(
+        &mut self,
+        state: &mut GameState,
+        qply: u8,
+        ply: u8,
+        mut alpha: i32,
+        beta: i32,
+    ) -> i32 {
+        self.nodes += 1;
+        if self.nodes.is_multiple_of(CLOCK_CHECK_INTERVAL) && self.clock.check(CLOCK_CHECK_INTERVAL)
+            || self.clock.stopped()
+        {
+            return 0;
+        }
+        if rules::is_dead_position(state) {
+            return 0;
+        }
+        let stand_pat = eval_material_for_side_to_move(state);
+        if ply == MAX_PLY {
+            return stand_pat;
+        }
+
+        let in_check = rules::is_in_check(state, state.side_to_move);
+        let mut best = i32::MIN;
+        let moves = if in_check {
+            ordered_candidates(state, None)
+        } else {
+            if stand_pat >= beta {
+                return stand_pat;
+            }
+            alpha = alpha.max(stand_pat);
+            best = stand_pat;
+            // Captures that lose material in the exchange sort last and are
+            // cut off, so each capture's exchange is evaluated only once.
+            let mut moves = movegen::generate_captures(state);
+            let mut losing = 0;
+            moves.sort_by_key(|mv| {
+                let key = move_order_key(state, mv);
+                if key.0 == 2 && !mv.is_promotion() {
+                    losing += 1;
+                    (3, 0)
+                } else {
+                    key
+                }
+            });
+            moves.truncate(moves.len() - losing);
+            if qply == 0 {
+                for mv in &movegen::generate_quiets(state) {
+                    if rules::gives_check(state, mv) {
+                        moves.push(mv);
+                    }
+                }
+            }
+            moves
+        };
+
+        let mut found_legal = false;
+        for mv in &moves {
+            if !in_check && rules::is_capture(state, mv) && !mv.is_promotion() {
+                // Delta pruning: even winning the piece outright would not
+                // lift the score back to alpha.
+                let gain =
+                    rules::captured_piece(state, mv).map_or(0, |piece| material_value(piece.kind));
+                if stand_pat + gain + DELTA_MARGIN <= alpha {
+                    continue;
+                }
+            }
+            let Some(record) = rules::make_move(state, mv) else {
+                continue;
+            };
+            found_legal = true;
+            let score = -self.quiesce(state, qply + 1, ply + 1, -beta, -alpha);
+            rules::unmake_move(state, &record);
+            if score > best {
+                best = score;
+            }
+            if score > alpha {
+                alpha = score;
+            }
+            if alpha >= beta {
+                break;
+            }
+        }
+        if in_check && !found_legal {
+            return terminal_score(state, ply);
+        }
+        best
     }
-    best
 }
 
-fn terminal_score(state: &GameState) -> i32 {
+// Mate scores in the table count from the position stored rather than from
+// the root, so they stay correct when the position is reached at another ply.
+fn score_to_tt(score: i32, ply: u8) -> i32 {
+    if is_mate_score(score) {
+        score + score.signum() * ply as i32
+    } else {
+        score
+    }
+}
+
+fn score_from_tt(score: i32, ply: u8) -> i32 {
+    if is_mate_score(score) {
+        score - score.signum() * ply as i32
+    } else {
+        score
+    }
+}
+
+fn is_mate_score(score: i32) -> bool {
+    score.abs() > MATE_SCORE - MAX_PLY as i32
+}
+
+fn terminal_score(state: &GameState, ply: u8) -> i32 {
     if rules::is_in_check(state, state.side_to_move) {
-        -MATE_SCORE
+        -(MATE_SCORE - ply as i32)
     } else {
         0
     }
@@ -136,47 +634,145 @@ fn terminal_score(state: &GameState) -> i32 {
 fn eval_material_for_side_to_move(state: &GameState) -> i32 {
     let us = state.side_to_move;
     let mut score = 0;
-    for rank in 0..8 {
-        for file in 0..8 {
-            if let Some(piece) = state.board[rank][file] {
-                let value = match piece.kind {
-                    PieceKind::Pawn => 1,
-                    PieceKind::Knight => 3,
-                    PieceKind::Bishop => 3,
-                    PieceKind::Rook => 5,
-                    PieceKind::Queen => 9,
-                    PieceKind::King => 0,
-                };
-                if piece.color == us {
-                    score += value;
-                } else {
-                    score -= value;
-                }
-            }
-        }
+    for kind in [
+        PieceKind::Pawn,
+        PieceKind::Knight,
+        PieceKind::Bishop,
+        PieceKind::Rook,
+        PieceKind::Queen,
+    ] {
+        let ours = state.board.pieces(us, kind).count_ones() as i32;
+        let theirs = state.board.pieces(us.opposite(), kind).count_ones() as i32;
+        score += material_value(kind) * (ours - theirs);
     }
     score
 }
 
-fn move_order_key(state: &GameState, mv: Move) -> u8 {
-    let is_promo = matches!(mv.kind, MoveKind::Promotion(_));
-    let is_capture = match mv.kind {
-        MoveKind::EnPassant => true,
-        MoveKind::CastleKingside | MoveKind::CastleQueenside => false,
-        MoveKind::Promotion(_) | MoveKind::Normal => piece_at(&state.board, mv.to).is_some(),
-    };
-    let mut key = 3;
-    if is_promo {
-        key -= 2;
+// In pawns, the unit the evaluation uses.
+fn material_value(kind: PieceKind) -> i32 {
+    match kind {
+        PieceKind::Pawn => 1,
+        PieceKind::Knight | PieceKind::Bishop => 3,
+        PieceKind::Rook => 5,
+        PieceKind::Queen => 9,
+        PieceKind::King => 0,
     }
-    if is_capture {
-        key -= 1;
+}
+
+// Winning and even captures and promotions first, best exchange first, then
+// quiet moves, then captures that lose material.
+fn move_order_key(state: &GameState, mv: Move) -> (u8, i32) {
+    if !rules::is_capture(state, mv) && !mv.is_promotion() {
+        return (1, 0);
+    }
+    let exchange = rules::see(state, mv);
+    if exchange >= 0 {
+        (0, -exchange)
+    } else {
+        (2, -exchange)
     }
-    key
 }
 
-fn ordered_candidates(state: &GameState) -> Vec<Move> {
-    let mut moves = movegen::generate_candidates(state);
-    moves.sort_by_key(|mv| move_order_key(state, *mv));
+// The transposition table's move, when there is one, goes before the rest.
+fn ordered_candidates(state: &GameState, tt_move: Option<Move>) -> MoveList {
+    let mut moves = if rules::is_in_check(state, state.side_to_move) {
+        movegen::generate_evasions(state)
+    } else {
+        movegen::generate_candidates(state)
+    };
+    moves.sort_by_key(|mv| (Some(mv) != tt_move, move_order_key(state, mv)));
     moves
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::moves::MoveKind;
+
+    fn search(fen: &str, depth: u8) -> SearchInfo {
+        let mut engine = Engine::new();
+        engine.set_fen(fen).unwrap();
+        let limits = SearchLimits {
+            depth: Some(depth),
+            ..SearchLimits::default()
+        };
+        engine
+            .go(&limits, &SearchControl::new(false), |_| {})
+            .unwrap()
+    }
+
+    #[test]
+    fn quiescence_sees_the_recapture() {
+        // Qxd5 wins a pawn at depth 1 only if exd5 is never looked at.
+        let info = search("4k3/8/4p3/3p4/8/8/8/3QK3 w - - 0 1", 1);
+        assert_ne!(info.best_move.to, (3, 4));
+        assert_eq!(info.score, 7);
+    }
+
+    #[test]
+    fn quiescence_finishes_the_exchange() {
+        // Nxd5 Nxd5 Rxd5 nets a pawn, but only with the second capture seen.
+        let info = search("4k3/8/5n2/3p4/1N6/8/8/3RK3 w - - 0 1", 1);
+        assert_eq!((info.best_move.from, info.best_move.to), ((1, 3), (3, 4)));
+        assert_eq!(info.score, 5);
+    }
+
+    #[test]
+    fn quiescence_finds_mate_by_a_quiet_check() {
+        // Rxd7 wins a knight, but leaves the back rank to Re1#, a quiet move
+        // only the first quiescence ply looks at.
+        let info = search("4r2k/3n2pp/8/8/8/8/5PPP/3R2K1 w - - 0 1", 1);
+        assert_ne!(info.best_move.to, (3, 6));
+        assert_eq!(info.score, -2);
+    }
+
+    #[test]
+    fn quiescence_skips_captures_that_cannot_reach_alpha() {
+        let mut state = GameState::from_fen("4k3/8/8/3p4/8/8/8/3QK3 w - - 0 1").unwrap();
+        let nodes = AtomicU64::new(0);
+        let abort = AtomicBool::new(false);
+        let clock = Clock {
+            control: None,
+            start: Instant::now(),
+            hard_limit: None,
+            node_limit: None,
+            nodes: &nodes,
+            abort: &abort,
+        };
+        let tt = TranspositionTable::new(1);
+        let mut searcher = Searcher {
+            clock: &clock,
+            tt: &tt,
+            nodes: 0,
+        };
+
+        // Up 8, Qxd5 could reach 9 plus the margin: enough to beat 10 but
+        // not 11.
+        assert_eq!(searcher.quiesce(&mut state, 1, 1, 10, 20), 9);
+        assert!(searcher.nodes > 1);
+        searcher.nodes = 0;
+        assert_eq!(searcher.quiesce(&mut state, 1, 1, 11, 20), 8);
+        assert_eq!(searcher.nodes, 1);
+    }
+
+    #[test]
+    fn mates_found_past_the_depth_limit_are_mate_scores() {
+        let ply = MAX_DEPTH + 10;
+        let score = MATE_SCORE - ply as i32;
+        assert_eq!(score_from_tt(score_to_tt(score, ply), ply), score);
+        assert_eq!(score_to_tt(score, ply), MATE_SCORE);
+        let info = SearchInfo {
+            best_move: Move {
+                from: (0, 0),
+                to: (0, 1),
+                kind: MoveKind::Normal,
+            },
+            score: -score,
+            depth: 1,
+            nodes: 1,
+            elapsed: Duration::ZERO,
+            hashfull: 0,
+        };
+        assert_eq!(info.mate(), Some(-37));
+    }
+}
@@ -1,53 +1,305 @@
-use crate::moves::Move;
+use crate::board::Color;
+use crate::moves::{Move, MoveRecord};
 use crate::rules;
-use crate::state::GameState;
+use crate::state::{FenError, GameState};
 
 #[derive(Debug)]
 pub struct IllegalMove;
 
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum WinReason {
+    Checkmate,
+    Resignation,
+    Timeout,
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum DrawReason {
+    Stalemate,
+    ThreefoldRepetition,
+    FivefoldRepetition,
+    FiftyMoveRule,
+    SeventyFiveMoveRule,
+    InsufficientMaterial,
+    DeadPosition,
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum GameOutcome {
+    Ongoing,
+    Win { winner: Color, reason: WinReason },
+    Draw(DrawReason),
+}
+
+impl GameOutcome {
+    pub fn is_over(self) -> bool {
+        self != GameOutcome::Ongoing
+    }
+
+    pub fn winner(self) -> Option<Color> {
+        match self {
+            GameOutcome::Win { winner, .. } => Some(winner),
+            GameOutcome::Ongoing | GameOutcome::Draw(_) => None,
+        }
+    }
+
+    pub fn result(self) -> GameResult {
+        match self {
+            GameOutcome::Ongoing => GameResult::Ongoing,
+            GameOutcome::Win {
+                winner: Color::White,
+                ..
+            } => GameResult::WhiteWins,
+            GameOutcome::Win {
+                winner: Color::Black,
+                ..
+            } => GameResult::BlackWins,
+            GameOutcome::Draw(_) => GameResult::Draw,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum GameResult {
+    WhiteWins,
+    BlackWins,
+    Draw,
+    Ongoing,
+}
+
+impl GameResult {
+    pub fn pgn_tag(self) -> &'static str {
+        match self {
+            GameResult::WhiteWins => "1-0",
+            GameResult::BlackWins => "0-1",
+            GameResult::Draw => "1/2-1/2",
+            GameResult::Ongoing => "*",
+        }
+    }
+}
+
+#[derive(Clone)]
 pub struct Game {
     pub state: GameState,
+    positions: Vec<u64>,
+    // Set by events that end the game off the board: resignation, timeout
+    // or a successful draw claim.
+    declared_outcome: Option<GameOutcome>,
+    history: Vec<MoveRecord>,
+    redo_stack: Vec<Move>,
+}
+
+impl Default for Game {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Game {
     pub fn new() -> Self {
-        Self {
-            state: GameState::new(),
-        }
+        Self::from_state(GameState::new())
     }
 
-    pub fn from_fen(fen: &str) -> Option<Self> {
-        Some(Self {
-            state: GameState::from_fen(fen)?,
-        })
+    pub fn from_fen(fen: &str) -> Result<Self, FenError> {
+        let state = GameState::from_fen(fen)?;
+        state.validate()?;
+        Ok(Self::from_state(state))
+    }
+
+    fn from_state(state: GameState) -> Self {
+        let positions = vec![rules::position_key(&state)];
+        Self {
+            state,
+            positions,
+            declared_outcome: None,
+            history: Vec::new(),
+            redo_stack: Vec::new(),
+        }
     }
 
     pub fn legal_moves(&self) -> Vec<Move> {
-        rules::legal_moves(&self.state)
+        rules::legal_moves(&self.state).to_vec()
     }
 
+    // Only legality is checked: a game whose outcome is decided still takes
+    // moves, since GUIs often play on past a draw the rules impose.
     pub fn make_move(&mut self, mv: Move) -> Result<(), IllegalMove> {
         if rules::is_move_legal(&self.state, mv) {
-            rules::apply_move_unchecked(&mut self.state, mv);
+            self.play(mv);
+            self.redo_stack.clear();
             Ok(())
         } else {
             Err(IllegalMove)
         }
     }
 
-    pub fn is_checkmate(&self) -> bool {
-        rules::is_checkmate(&self.state)
+    fn play(&mut self, mv: Move) {
+        let record = rules::apply_move_unchecked(&mut self.state, mv);
+        self.history.push(record);
+        self.positions.push(rules::position_key(&self.state));
+    }
+
+    // Taking a move back also withdraws a resignation, timeout or draw claim.
+    pub fn undo_move(&mut self) -> Option<Move> {
+        let record = self.history.pop()?;
+        rules::unmake_move(&mut self.state, &record);
+        self.positions.pop();
+        self.declared_outcome = None;
+        self.redo_stack.push(record.mv);
+        Some(record.mv)
+    }
+
+    pub fn redo_move(&mut self) -> Option<Move> {
+        let mv = self.redo_stack.pop()?;
+        self.play(mv);
+        Some(mv)
+    }
+
+    pub fn history(&self) -> &[MoveRecord] {
+        &self.history
+    }
+
+    pub fn moves(&self) -> Vec<Move> {
+        self.history.iter().map(|record| record.mv).collect()
+    }
+
+    pub fn halfmove_clock(&self) -> u32 {
+        self.state.halfmove_clock
+    }
+
+    pub fn fullmove_number(&self) -> u32 {
+        self.state.fullmove_number
+    }
+
+    pub fn repetition_count(&self) -> usize {
+        let Some(&current) = self.positions.last() else {
+            return 0;
+        };
+        // Only positions since the last capture or pawn move with the same
+        // side to move can repeat the current one.
+        self.positions
+            .iter()
+            .rev()
+            .take(self.state.halfmove_clock as usize + 1)
+            .step_by(2)
+            .filter(|&&key| key == current)
+            .count()
+    }
+
+    pub fn is_threefold_repetition(&self) -> bool {
+        self.repetition_count() >= 3
+    }
+
+    pub fn is_fivefold_repetition(&self) -> bool {
+        self.repetition_count() >= 5
+    }
+
+    pub fn can_claim_fifty_move_draw(&self) -> bool {
+        rules::can_claim_fifty_move_draw(&self.state)
+    }
+
+    pub fn is_seventy_five_move_draw(&self) -> bool {
+        rules::is_seventy_five_move_draw(&self.state)
+    }
+
+    pub fn is_insufficient_material(&self) -> bool {
+        rules::is_insufficient_material(&self.state)
+    }
+
+    pub fn is_dead_position(&self) -> bool {
+        rules::is_dead_position(&self.state)
+    }
+
+    pub fn outcome(&self) -> GameOutcome {
+        if let Some(outcome) = self.declared_outcome {
+            return outcome;
+        }
+        if self.legal_moves().is_empty() {
+            let side = self.state.side_to_move;
+            return if rules::is_in_check(&self.state, side) {
+                GameOutcome::Win {
+                    winner: side.opposite(),
+                    reason: WinReason::Checkmate,
+                }
+            } else {
+                GameOutcome::Draw(DrawReason::Stalemate)
+            };
+        }
+        if self.is_fivefold_repetition() {
+            GameOutcome::Draw(DrawReason::FivefoldRepetition)
+        } else if self.is_seventy_five_move_draw() {
+            GameOutcome::Draw(DrawReason::SeventyFiveMoveRule)
+        } else if self.is_insufficient_material() {
+            GameOutcome::Draw(DrawReason::InsufficientMaterial)
+        } else if self.is_dead_position() {
+            GameOutcome::Draw(DrawReason::DeadPosition)
+        } else {
+            GameOutcome::Ongoing
+        }
+    }
+
+    pub fn result(&self) -> GameResult {
+        self.outcome().result()
     }
 
-    pub fn is_stalemate(&self) -> bool {
-        rules::is_stalemate(&self.state)
+    pub fn claim_draw(&mut self) -> bool {
+        if self.outcome().is_over() {
+            return false;
+        }
+        let reason = if self.is_threefold_repetition() {
+            DrawReason::ThreefoldRepetition
+        } else if self.can_claim_fifty_move_draw() {
+            DrawReason::FiftyMoveRule
+        } else {
+            return false;
+        };
+        self.declared_outcome = Some(GameOutcome::Draw(reason));
+        true
+    }
+
+    pub fn resign(&mut self, color: Color) {
+        self.declare_win(color.opposite(), WinReason::Resignation);
+    }
+
+    // Running out of time only loses if the opponent could still mate; if
+    // not, the game is drawn.
+    pub fn lose_on_time(&mut self, color: Color) {
+        if !rules::cannot_checkmate(&self.state, color.opposite()) {
+            self.declare_win(color.opposite(), WinReason::Timeout);
+        } else if !self.outcome().is_over() {
+            self.declared_outcome = Some(GameOutcome::Draw(DrawReason::InsufficientMaterial));
+        }
+    }
+
+    fn declare_win(&mut self, winner: Color, reason: WinReason) {
+        if !self.outcome().is_over() {
+            self.declared_outcome = Some(GameOutcome::Win { winner, reason });
+        }
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::Game;
+    use super::{DrawReason, Game, GameOutcome, GameResult, WinReason};
+    use crate::board::{Color, PieceKind};
     use crate::rules;
+    use crate::state::{FenErrorKind, PositionError};
+
+    fn play(game: &mut Game, from: (u8, u8), to: (u8, u8)) {
+        let mv = game
+            .legal_moves()
+            .into_iter()
+            .find(|m| m.from == from && m.to == to)
+            .unwrap();
+        game.make_move(mv).unwrap();
+    }
+
+    fn shuffle_knights(game: &mut Game) {
+        play(game, (6, 0), (5, 2));
+        play(game, (6, 7), (5, 5));
+        play(game, (5, 2), (6, 0));
+        play(game, (5, 5), (6, 7));
+    }
 
     #[test]
     fn initial_position_has_20_legal_moves() {
@@ -55,9 +307,292 @@ mod tests {
         assert_eq!(game.legal_moves().len(), 20);
     }
 
+    #[test]
+    fn move_counters_advance_through_make_move() {
+        let mut game = Game::new();
+        play(&mut game, (6, 0), (5, 2));
+        play(&mut game, (6, 7), (5, 5));
+        play(&mut game, (5, 2), (6, 0));
+        assert_eq!(game.halfmove_clock(), 3);
+        assert_eq!(game.fullmove_number(), 2);
+    }
+
     #[test]
     fn initial_position_not_in_check() {
         let game = Game::new();
         assert!(!rules::is_in_check(&game.state, game.state.side_to_move));
     }
+
+    #[test]
+    fn threefold_and_fivefold_repetition() {
+        let mut game = Game::new();
+        assert_eq!(game.repetition_count(), 1);
+
+        shuffle_knights(&mut game);
+        assert_eq!(game.repetition_count(), 2);
+        assert!(!game.is_threefold_repetition());
+
+        shuffle_knights(&mut game);
+        assert!(game.is_threefold_repetition());
+        assert!(!game.is_fivefold_repetition());
+
+        shuffle_knights(&mut game);
+        shuffle_knights(&mut game);
+        assert!(game.is_fivefold_repetition());
+    }
+
+    #[test]
+    fn pawn_move_breaks_repetition_history() {
+        let mut game = Game::new();
+        shuffle_knights(&mut game);
+        shuffle_knights(&mut game);
+        play(&mut game, (4, 1), (4, 3));
+        assert_eq!(game.repetition_count(), 1);
+    }
+
+    #[test]
+    fn uncapturable_en_passant_square_does_not_break_repetition() {
+        // After 1. e4 the e3 square is set but no black pawn can capture, so
+        // returning to this position later counts as a repetition.
+        let mut game = Game::from_fen("4k3/8/8/8/8/8/4P3/4K3 w - - 0 1").unwrap();
+        play(&mut game, (4, 1), (4, 3));
+        assert!(game.state.en_passant.is_some());
+        for _ in 0..2 {
+            play(&mut game, (4, 7), (3, 7));
+            play(&mut game, (4, 0), (3, 0));
+            play(&mut game, (3, 7), (4, 7));
+            play(&mut game, (3, 0), (4, 0));
+        }
+        assert!(game.is_threefold_repetition());
+    }
+
+    #[test]
+    fn fifty_move_claim_becomes_available() {
+        let mut game = Game::from_fen("4k3/8/8/8/8/8/8/R3K3 w - - 98 80").unwrap();
+        assert!(!game.can_claim_fifty_move_draw());
+        play(&mut game, (0, 0), (0, 1));
+        assert!(!game.can_claim_fifty_move_draw());
+        play(&mut game, (4, 7), (3, 7));
+        assert!(game.can_claim_fifty_move_draw());
+        assert!(!game.is_seventy_five_move_draw());
+    }
+
+    #[test]
+    fn seventy_five_move_rule_ends_the_game() {
+        let mut game = Game::from_fen("4k3/8/8/8/8/8/8/R3K3 b - - 149 80").unwrap();
+        play(&mut game, (4, 7), (3, 7));
+        assert!(game.is_seventy_five_move_draw());
+        assert_eq!(
+            game.outcome(),
+            GameOutcome::Draw(DrawReason::SeventyFiveMoveRule)
+        );
+
+        let mv = game.legal_moves()[0];
+        assert!(game.make_move(mv).is_ok());
+        assert_eq!(
+            game.outcome(),
+            GameOutcome::Draw(DrawReason::SeventyFiveMoveRule)
+        );
+    }
+
+    #[test]
+    fn capturing_last_pawn_leaves_insufficient_material() {
+        let mut game = Game::from_fen("4k3/8/8/8/8/8/3p4/4K3 w - - 0 1").unwrap();
+        assert!(!game.is_insufficient_material());
+        play(&mut game, (4, 0), (3, 1));
+        assert!(game.is_insufficient_material());
+        assert!(game.is_dead_position());
+    }
+
+    #[test]
+    fn new_game_is_ongoing() {
+        let game = Game::new();
+        assert_eq!(game.outcome(), GameOutcome::Ongoing);
+        assert_eq!(game.result().pgn_tag(), "*");
+    }
+
+    #[test]
+    fn fools_mate_is_checkmate() {
+        let mut game = Game::new();
+        play(&mut game, (5, 1), (5, 2));
+        play(&mut game, (4, 6), (4, 4));
+        play(&mut game, (6, 1), (6, 3));
+        play(&mut game, (3, 7), (7, 3));
+        assert_eq!(
+            game.outcome(),
+            GameOutcome::Win {
+                winner: Color::Black,
+                reason: WinReason::Checkmate,
+            }
+        );
+        assert_eq!(game.result(), GameResult::BlackWins);
+        assert_eq!(game.result().pgn_tag(), "0-1");
+    }
+
+    #[test]
+    fn stalemate_outcome() {
+        let game = Game::from_fen("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1").unwrap();
+        assert_eq!(game.outcome(), GameOutcome::Draw(DrawReason::Stalemate));
+        assert_eq!(game.result().pgn_tag(), "1/2-1/2");
+    }
+
+    #[test]
+    fn resignation_and_timeout_end_the_game() {
+        let mut game = Game::new();
+        game.resign(Color::White);
+        assert_eq!(
+            game.outcome(),
+            GameOutcome::Win {
+                winner: Color::Black,
+                reason: WinReason::Resignation,
+            }
+        );
+        let mv = game.legal_moves()[0];
+        assert!(game.make_move(mv).is_ok());
+        assert_eq!(game.outcome().winner(), Some(Color::Black));
+
+        game.lose_on_time(Color::Black);
+        assert_eq!(game.outcome().winner(), Some(Color::Black));
+
+        let mut game = Game::new();
+        game.lose_on_time(Color::Black);
+        assert_eq!(
+            game.outcome(),
+            GameOutcome::Win {
+                winner: Color::White,
+                reason: WinReason::Timeout,
+            }
+        );
+        assert_eq!(game.result().pgn_tag(), "1-0");
+    }
+
+    #[test]
+    fn timeout_is_a_draw_when_the_opponent_cannot_mate() {
+        let mut game = Game::from_fen("4k3/8/8/8/8/8/8/3QK3 w - - 0 1").unwrap();
+        game.lose_on_time(Color::White);
+        assert_eq!(
+            game.outcome(),
+            GameOutcome::Draw(DrawReason::InsufficientMaterial)
+        );
+        assert_eq!(game.result().pgn_tag(), "1/2-1/2");
+
+        let mut game = Game::from_fen("4k3/8/8/8/8/8/8/3QK3 w - - 0 1").unwrap();
+        game.lose_on_time(Color::Black);
+        assert_eq!(game.outcome().winner(), Some(Color::White));
+
+        // A lone knight can still mate a king hemmed in by its own pawn.
+        let mut game = Game::from_fen("4k3/4p3/8/8/8/8/8/3NK3 b - - 0 1").unwrap();
+        game.lose_on_time(Color::Black);
+        assert_eq!(game.outcome().winner(), Some(Color::White));
+    }
+
+    #[test]
+    fn draw_claims_require_grounds() {
+        let mut game = Game::new();
+        assert!(!game.claim_draw());
+        assert_eq!(game.outcome(), GameOutcome::Ongoing);
+
+        shuffle_knights(&mut game);
+        shuffle_knights(&mut game);
+        assert_eq!(game.outcome(), GameOutcome::Ongoing);
+        assert!(game.claim_draw());
+        assert_eq!(
+            game.outcome(),
+            GameOutcome::Draw(DrawReason::ThreefoldRepetition)
+        );
+
+        let mut game = Game::from_fen("4k3/8/8/8/8/8/8/R3K3 w - - 100 80").unwrap();
+        assert!(game.claim_draw());
+        assert_eq!(game.outcome(), GameOutcome::Draw(DrawReason::FiftyMoveRule));
+    }
+
+    #[test]
+    fn automatic_draws() {
+        let mut game = Game::new();
+        for _ in 0..4 {
+            shuffle_knights(&mut game);
+        }
+        assert_eq!(
+            game.outcome(),
+            GameOutcome::Draw(DrawReason::FivefoldRepetition)
+        );
+
+        let game = Game::from_fen("4k3/8/8/8/8/8/8/4KB2 w - - 0 1").unwrap();
+        assert_eq!(
+            game.outcome(),
+            GameOutcome::Draw(DrawReason::InsufficientMaterial)
+        );
+
+        let game = Game::from_fen("8/3k4/8/p1p1p1p1/P1P1P1P1/8/3K4/8 w - - 0 1").unwrap();
+        assert_eq!(game.outcome(), GameOutcome::Draw(DrawReason::DeadPosition));
+    }
+
+    #[test]
+    fn from_fen_rejects_illegal_positions() {
+        let err = Game::from_fen("4k3/8/8/8/8/8/8/4R1K1 w - - 0 1")
+            .err()
+            .unwrap();
+        assert_eq!(err.field, 1);
+        assert_eq!(
+            err.kind,
+            FenErrorKind::IllegalPosition(PositionError::OpponentInCheck)
+        );
+    }
+
+    #[test]
+    fn undo_and_redo_walk_the_history() {
+        let mut game = Game::new();
+        let start = game.state.clone();
+        play(&mut game, (4, 1), (4, 3));
+        play(&mut game, (3, 6), (3, 4));
+        play(&mut game, (4, 3), (3, 4));
+        let after_capture = game.state.clone();
+
+        assert_eq!(game.moves().len(), 3);
+        let capture = game.history()[2];
+        assert_eq!(
+            capture.captured.map(|piece| piece.kind),
+            Some(PieceKind::Pawn)
+        );
+        assert_eq!(capture.prior_en_passant, Some((3, 5)));
+
+        assert_eq!(game.undo_move(), Some(capture.mv));
+        assert_eq!(game.undo_move().map(|mv| mv.to), Some((3, 4)));
+        assert_eq!(game.undo_move().map(|mv| mv.to), Some((4, 3)));
+        assert_eq!(game.undo_move(), None);
+        assert_eq!(game.state, start);
+        assert!(game.moves().is_empty());
+
+        for _ in 0..3 {
+            assert!(game.redo_move().is_some());
+        }
+        assert_eq!(game.redo_move(), None);
+        assert_eq!(game.state, after_capture);
+        assert_eq!(game.moves().len(), 3);
+    }
+
+    #[test]
+    fn new_move_discards_redo_stack() {
+        let mut game = Game::new();
+        play(&mut game, (4, 1), (4, 3));
+        game.undo_move();
+        play(&mut game, (3, 1), (3, 3));
+        assert_eq!(game.redo_move(), None);
+        assert_eq!(game.moves()[0].to, (3, 3));
+    }
+
+    #[test]
+    fn undo_restores_repetition_history_and_outcome() {
+        let mut game = Game::new();
+        shuffle_knights(&mut game);
+        shuffle_knights(&mut game);
+        assert!(game.is_threefold_repetition());
+        game.undo_move();
+        assert!(!game.is_threefold_repetition());
+
+        game.resign(Color::Black);
+        assert!(game.outcome().is_over());
+        game.undo_move();
+        assert_eq!(game.outcome(), GameOutcome::Ongoing);
+    }
 }
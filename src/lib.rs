@@ -1,13 +1,18 @@
+pub mod bitboard;
 pub mod board;
 pub mod dirs;
 pub mod engine;
 pub mod game;
+pub mod magic;
 pub mod movegen;
 pub mod moves;
 pub mod rules;
 pub mod state;
+pub mod timeman;
+pub mod tt;
+pub mod zobrist;
 
 pub use board::{Color, Piece, PieceKind, Square};
-pub use game::Game;
-pub use moves::{Move, MoveKind};
-pub use state::{CastlingRights, GameState};
+pub use game::{DrawReason, Game, GameOutcome, GameResult, WinReason};
+pub use moves::{Move, MoveKind, MoveList, MoveRecord, PackedMove};
+pub use state::{CastlingRights, FenError, FenErrorKind, GameState, PositionError};
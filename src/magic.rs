@@ -0,0 +1,328 @@
+use std::sync::LazyLock;
+
+use crate::bitboard::{Bitboard, index_square, ray_attacks, square_bit};
+use crate::dirs::{BISHOP_DIRS, ROOK_DIRS};
+
+struct Magic {
+    mask: Bitboard,
+    magic: u64,
+    shift: u32,
+    offset: usize,
+}
+
+impl Magic {
+    fn index(&self, occupied: Bitboard) -> usize {
+        self.offset + ((occupied & self.mask).wrapping_mul(self.magic) >> self.shift) as usize
+    }
+}
+
+struct MagicTables {
+    bishops: Vec<Magic>,
+    rooks: Vec<Magic>,
+    attacks: Vec<Bitboard>,
+}
+
+// Generated by `cargo run --release --example gen_magics`.
+const BISHOP_MAGICS: [u64; 64] = [
+    0x0020428400408200,
+    0x2008010104210004,
+    0x02d0009200480190,
+    0x0018158b00010100,
+    0x02c4042132048008,
+    0x020082202000c221,
+    0x4000421050080009,
+    0x0210140202022020,
+    0x00c0101410042248,
+    0x0405204800d48080,
+    0x3800c89200420002,
+    0x180844124a020440,
+    0x04403410a8002221,
+    0x4040209004200400,
+    0x084004020202a204,
+    0x3010002104022000,
+    0x00200240a9110900,
+    0x2302800404080210,
+    0x0204188800240010,
+    0x8048000c01401200,
+    0x120c001a11040900,
+    0x0000401200500440,
+    0x00004040840420a0,
+    0x0020930822880804,
+    0x4044401090900161,
+    0x0034100015210804,
+    0x8004100009010120,
+    0x48c8080000820500,
+    0x0080848004002000,
+    0x0801004012005044,
+    0x000080902c040400,
+    0x0004009005004100,
+    0x0b103010048a0200,
+    0x8004100203181a00,
+    0x0800140200100080,
+    0x8401010800910040,
+    0x0840010011290040,
+    0x40100214202e1000,
+    0x0842040040010840,
+    0x0028010040010860,
+    0x00080202a2051000,
+    0x4200841008084204,
+    0x0021120110000d02,
+    0x48c1004208000084,
+    0x0010088100414400,
+    0x0021101000420580,
+    0x0010040558401410,
+    0x200c0c82a1050205,
+    0x0011108820088000,
+    0x0001011910120402,
+    0x1580008608091248,
+    0x8010018020880c02,
+    0x20a1101032088480,
+    0x0080100408082800,
+    0x28100401140401c0,
+    0x8002102200930012,
+    0x4001040082080200,
+    0x082200a498081808,
+    0x000508610080d003,
+    0x0052020044842402,
+    0x4800a00140c84840,
+    0x5000000848080820,
+    0x0101086004240040,
+    0x0028280808005014,
+];
+
+const ROOK_MAGICS: [u64; 64] = [
+    0x1080004008801020,
+    0x0840092002c03000,
+    0x1900200010400900,
+    0x0880100008000480,
+    0x4200100420080200,
+    0x8100020100080400,
+    0x0200040110886200,
+    0x0200008040220411,
+    0x0404800084400220,
+    0x0000401000402000,
+    0x0086001081220440,
+    0x0408800800100280,
+    0x000a001201040820,
+    0x8848800200840080,
+    0x4001000100040200,
+    0x0442000102105084,
+    0x9080010020804100,
+    0x0040404000201009,
+    0x0000808010002009,
+    0x2200090021d00100,
+    0x0008008008040080,
+    0x0004004002010040,
+    0x0011040008015042,
+    0x00000a0001768104,
+    0x0000800080204009,
+    0x2010004140002001,
+    0x9800200280100080,
+    0x1000100080080080,
+    0x0442000a00049020,
+    0x2100040080020080,
+    0x0800120400900148,
+    0x0010040a00128541,
+    0x2800804000800030,
+    0x1010002000400041,
+    0x4000200011004100,
+    0x0610008410800800,
+    0x0400802402800800,
+    0xc100020080800400,
+    0x0002000802000401,
+    0x0182085882000401,
+    0x0220204000808000,
+    0x2860100040024022,
+    0x0001002004110040,
+    0x99101042000a0020,
+    0x0004080004008080,
+    0x0010040002008080,
+    0x2012004881020004,
+    0x8300842444820011,
+    0x0088403882010200,
+    0x0820400080210100,
+    0x0110910040a00300,
+    0x0801100280080480,
+    0x0242009008200600,
+    0x1002000489500200,
+    0x0040800200010080,
+    0x0091800041000080,
+    0x0000209300488001,
+    0x04c1002414824001,
+    0x020020000b001041,
+    0x7000100004200901,
+    0x8002002004100802,
+    0x30010002084c0007,
+    0x0888221800813004,
+    0x4000002840840112,
+];
+
+static TABLES: LazyLock<MagicTables> = LazyLock::new(build_tables);
+
+pub fn bishop_attacks(sq: usize, occupied: Bitboard) -> Bitboard {
+    let tables = &*TABLES;
+    tables.attacks[tables.bishops[sq].index(occupied)]
+}
+
+pub fn rook_attacks(sq: usize, occupied: Bitboard) -> Bitboard {
+    let tables = &*TABLES;
+    tables.attacks[tables.rooks[sq].index(occupied)]
+}
+
+fn build_tables() -> MagicTables {
+    let mut attacks = Vec::new();
+    let bishops = (0..64)
+        .map(|sq| fill_table(sq, &BISHOP_DIRS, BISHOP_MAGICS[sq], &mut attacks))
+        .collect();
+    let rooks = (0..64)
+        .map(|sq| fill_table(sq, &ROOK_DIRS, ROOK_MAGICS[sq], &mut attacks))
+        .collect();
+    MagicTables {
+        bishops,
+        rooks,
+        attacks,
+    }
+}
+
+fn fill_table(index: usize, dirs: &[(i8, i8)], magic: u64, attacks: &mut Vec<Bitboard>) -> Magic {
+    let mask = relevant_mask(index, dirs);
+    let bits = mask.count_ones();
+    let entry = Magic {
+        mask,
+        magic,
+        shift: 64 - bits,
+        offset: attacks.len(),
+    };
+    attacks.resize(attacks.len() + (1 << bits), 0);
+    for (occupied, reach) in blocker_subsets(index, dirs) {
+        let slot = entry.index(occupied);
+        debug_assert!(attacks[slot] == 0 || attacks[slot] == reach);
+        attacks[slot] = reach;
+    }
+    entry
+}
+
+// Every blocker arrangement on the relevant mask, paired with the attack set
+// it produces.
+fn blocker_subsets(index: usize, dirs: &[(i8, i8)]) -> Vec<(Bitboard, Bitboard)> {
+    let sq = index_square(index);
+    let mask = relevant_mask(index, dirs);
+    let mut subsets = Vec::with_capacity(1 << mask.count_ones());
+    let mut subset: Bitboard = 0;
+    loop {
+        subsets.push((subset, ray_attacks(sq, subset, dirs)));
+        subset = subset.wrapping_sub(mask) & mask;
+        if subset == 0 {
+            break;
+        }
+    }
+    subsets
+}
+
+// Random search for a multiplier per square that maps every blocker subset to
+// a slot without destructive collisions. Used by `examples/gen_magics.rs` to
+// produce `BISHOP_MAGICS` and `ROOK_MAGICS`.
+pub fn find_magics(dirs: &[(i8, i8)], seed: u64) -> Vec<u64> {
+    let mut rng = XorShift(seed);
+    (0..64)
+        .map(|index| find_magic(index, dirs, &mut rng))
+        .collect()
+}
+
+fn find_magic(index: usize, dirs: &[(i8, i8)], rng: &mut XorShift) -> u64 {
+    let mask = relevant_mask(index, dirs);
+    let bits = mask.count_ones();
+    let subsets = blocker_subsets(index, dirs);
+    let mut table = vec![0; 1 << bits];
+    let mut epoch = vec![0u32; 1 << bits];
+    for attempt in 1.. {
+        let magic = rng.sparse();
+        if (mask.wrapping_mul(magic) >> 56).count_ones() < 6 {
+            continue;
+        }
+        let candidate = Magic {
+            mask,
+            magic,
+            shift: 64 - bits,
+            offset: 0,
+        };
+        let fits = subsets.iter().all(|&(occupied, reach)| {
+            let slot = candidate.index(occupied);
+            if epoch[slot] != attempt {
+                epoch[slot] = attempt;
+                table[slot] = reach;
+                true
+            } else {
+                table[slot] == reach
+            }
+        });
+        if fits {
+            return magic;
+        }
+    }
+    unreachable!()
+}
+
+// Squares whose occupancy can change the attack set: every ray square except
+// the last one before the edge.
+fn relevant_mask(index: usize, dirs: &[(i8, i8)]) -> Bitboard {
+    let sq = index_square(index);
+    let mut mask = 0;
+    for (df, dr) in dirs {
+        let mut file = sq.0 as i8 + df;
+        let mut rank = sq.1 as i8 + dr;
+        while (0..8).contains(&(file + df)) && (0..8).contains(&(rank + dr)) {
+            mask |= square_bit((file as u8, rank as u8));
+            file += df;
+            rank += dr;
+        }
+    }
+    mask
+}
+
+struct XorShift(u64);
+
+impl XorShift {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+
+    fn sparse(&mut self) -> u64 {
+        self.next() & self.next() & self.next()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn magic_lookups_match_ray_walk_on_random_occupancies() {
+        let mut rng = XorShift(0x9e37_79b9_7f4a_7c15);
+        for index in 0..64 {
+            let sq = index_square(index);
+            for _ in 0..200 {
+                let occupied = rng.next() & rng.next();
+                assert_eq!(
+                    bishop_attacks(index, occupied),
+                    ray_attacks(sq, occupied, &BISHOP_DIRS)
+                );
+                assert_eq!(
+                    rook_attacks(index, occupied),
+                    ray_attacks(sq, occupied, &ROOK_DIRS)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn relevant_masks_exclude_edges() {
+        assert_eq!(relevant_mask(0, &ROOK_DIRS).count_ones(), 12);
+        assert_eq!(relevant_mask(27, &ROOK_DIRS).count_ones(), 10);
+        assert_eq!(relevant_mask(0, &BISHOP_DIRS).count_ones(), 6);
+        assert_eq!(relevant_mask(27, &BISHOP_DIRS).count_ones(), 9);
+    }
+}
@@ -1,14 +1,25 @@
 use std::fs::{File, OpenOptions};
 use std::io::{self, BufRead, Write};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
 
 use rejectchess::board::{PieceKind, Square};
-use rejectchess::engine::Engine;
+use rejectchess::engine::{Engine, SearchControl, SearchLimits};
 use rejectchess::moves::{Move, MoveKind};
+use rejectchess::tt::{DEFAULT_HASH_MB, MAX_HASH_MB};
+
+// A search running on its own thread, so the loop keeps reading commands.
+struct Search {
+    control: Arc<SearchControl>,
+    handle: JoinHandle<()>,
+}
 
 fn main() {
     let mut log = open_log();
     let stdin = io::stdin();
     let mut engine = Engine::new();
+    let mut search: Option<Search> = None;
     for line in stdin.lock().lines() {
         let Ok(line) = line else { break };
         let line = line.trim();
@@ -19,29 +30,93 @@ fn main() {
         if line == "uci" {
             send(&mut log, "id name rejectchess");
             send(&mut log, "id author unknown");
+            send(
+                &mut log,
+                &format!(
+                    "option name Hash type spin default {} min 1 max {}",
+                    DEFAULT_HASH_MB, MAX_HASH_MB
+                ),
+            );
+            send(&mut log, "option name Clear Hash type button");
+            send(
+                &mut log,
+                "option name Move Overhead type spin default 30 min 0 max 5000",
+            );
             send(&mut log, "uciok");
         } else if line == "isready" {
             send(&mut log, "readyok");
+        } else if line == "stop" {
+            finish_search(&mut search);
+        } else if line == "ponderhit" {
+            if let Some(search) = &search {
+                search.control.ponderhit();
+            }
         } else if line == "ucinewgame" {
+            finish_search(&mut search);
             engine.reset();
+            engine.clear_hash();
+        } else if line.starts_with("setoption") {
+            finish_search(&mut search);
+            handle_setoption(line, &mut engine, &mut log);
         } else if line.starts_with("position") {
-            handle_position(line, &mut engine);
+            finish_search(&mut search);
+            handle_position(line, &mut engine, &mut log);
+        } else if line.starts_with("go perft") {
+            finish_search(&mut search);
+            handle_perft(line, &engine, &mut log);
         } else if line.starts_with("go") {
-            let best = engine.go();
-            match best {
-                Some((mv, score)) => {
-                    let depth = engine.search_depth();
-                    let mv_str = to_uci(mv);
-                    send(&mut log, &format!("info depth {} score cp {} pv {}", depth, score, mv_str));
-                    send(&mut log, &format!("bestmove {}", mv_str));
-                }
-                None => send(&mut log, "bestmove 0000"),
-            }
+            finish_search(&mut search);
+            let limits = parse_go(line, &mut log);
+            search = Some(start_search(&engine, limits, &log));
         } else if line == "quit" {
             break;
         }
         let _ = io::stdout().flush();
     }
+    finish_search(&mut search);
+}
+
+fn start_search(engine: &Engine, limits: SearchLimits, log: &Option<File>) -> Search {
+    let engine = engine.clone();
+    let control = Arc::new(SearchControl::new(limits.ponder));
+    let mut log = log.as_ref().and_then(|f| f.try_clone().ok());
+    let handle = thread::spawn({
+        let control = Arc::clone(&control);
+        move || {
+            let best = engine.go(&limits, &control, |info| {
+                let score = match info.mate() {
+                    Some(moves) => format!("mate {}", moves),
+                    None => format!("cp {}", info.score),
+                };
+                send(
+                    &mut log,
+                    &format!(
+                        "info depth {} score {} nodes {} time {} hashfull {} pv {}",
+                        info.depth,
+                        score,
+                        info.nodes,
+                        info.elapsed.as_millis(),
+                        info.hashfull,
+                        to_uci(info.best_move)
+                    ),
+                );
+            });
+            match best {
+                Some(info) => send(&mut log, &format!("bestmove {}", to_uci(info.best_move))),
+                None => send(&mut log, "bestmove 0000"),
+            }
+            let _ = io::stdout().flush();
+        }
+    });
+    Search { control, handle }
+}
+
+// Stops the running search, if any, and waits for its bestmove.
+fn finish_search(search: &mut Option<Search>) {
+    if let Some(search) = search.take() {
+        search.control.stop();
+        let _ = search.handle.join();
+    }
 }
 
 fn open_log() -> Option<File> {
@@ -68,7 +143,7 @@ fn send(log: &mut Option<File>, msg: &str) {
     log_line(log, ">>", msg);
 }
 
-fn handle_position(line: &str, engine: &mut Engine) {
+fn handle_position(line: &str, engine: &mut Engine, log: &mut Option<File>) {
     let mut parts = line.split_whitespace();
     let _ = parts.next();
 
@@ -91,7 +166,8 @@ fn handle_position(line: &str, engine: &mut Engine) {
                 fen_parts.push(part);
             }
             let fen = fen_parts.join(" ");
-            if !engine.set_fen(&fen) {
+            if let Err(err) = engine.set_fen(&fen) {
+                send(log, &format!("info string invalid fen: {}", err));
                 return;
             }
         }
@@ -99,18 +175,133 @@ fn handle_position(line: &str, engine: &mut Engine) {
     }
 
     if let Some(moves) = moves_iter {
+        // The rest of the list makes no sense once one move does not apply.
         for token in moves {
             let legal = engine.legal_moves();
-            let Some(mv) = parse_uci_move(token, &legal) else {
-                break;
-            };
-            if engine.apply_moves(&[mv]).is_err() {
+            let applied =
+                parse_uci_move(token, &legal).is_some_and(|mv| engine.apply_moves(&[mv]).is_ok());
+            if !applied {
+                send(log, &format!("info string illegal move: {}", token));
                 break;
             }
         }
     }
 }
 
+// Option names are matched without regard to case, as UCI allows.
+fn handle_setoption(line: &str, engine: &mut Engine, log: &mut Option<File>) {
+    let mut parts = line.split_whitespace().skip(1);
+    if parts.next() != Some("name") {
+        send(log, "info string missing option name");
+        return;
+    }
+    let mut name = Vec::new();
+    for part in parts.by_ref() {
+        if part == "value" {
+            break;
+        }
+        name.push(part);
+    }
+    let name = name.join(" ");
+    let value = parts.collect::<Vec<_>>().join(" ");
+    let value = Some(value.as_str()).filter(|value| !value.is_empty());
+
+    let result = match name.to_ascii_lowercase().as_str() {
+        "hash" => parse_value(value).and_then(|megabytes: usize| {
+            if !(1..=MAX_HASH_MB).contains(&megabytes) {
+                return Err(value);
+            }
+            engine.set_hash_size(megabytes);
+            Ok(())
+        }),
+        "clear hash" => {
+            engine.clear_hash();
+            Ok(())
+        }
+        "move overhead" => parse_value(value).map(|v: u64| {
+            engine.set_move_overhead(Duration::from_millis(v));
+        }),
+        _ => {
+            send(log, &format!("info string unknown option: {}", name));
+            return;
+        }
+    };
+    match result {
+        Ok(()) => {}
+        Err(Some(value)) => send(
+            log,
+            &format!("info string invalid {} value: {}", name, value),
+        ),
+        Err(None) => send(log, &format!("info string missing {} value", name)),
+    }
+}
+
+// Unknown or malformed parameters are reported and skipped; the search still
+// runs with whatever parsed.
+fn parse_go(line: &str, log: &mut Option<File>) -> SearchLimits {
+    let mut limits = SearchLimits::default();
+    let mut parts = line.split_whitespace().skip(1);
+    while let Some(key) = parts.next() {
+        let result = match key {
+            "infinite" => {
+                limits.infinite = true;
+                Ok(())
+            }
+            "ponder" => {
+                limits.ponder = true;
+                Ok(())
+            }
+            "wtime" => parse_value(parts.next()).map(|v| limits.wtime = Some(millis(v))),
+            "btime" => parse_value(parts.next()).map(|v| limits.btime = Some(millis(v))),
+            "winc" => parse_value(parts.next()).map(|v| limits.winc = Some(millis(v))),
+            "binc" => parse_value(parts.next()).map(|v| limits.binc = Some(millis(v))),
+            "movetime" => parse_value(parts.next()).map(|v| limits.movetime = Some(millis(v))),
+            "movestogo" => parse_value(parts.next()).map(|v| limits.movestogo = Some(v)),
+            "depth" => parse_value(parts.next()).map(|v| limits.depth = Some(v)),
+            "nodes" => parse_value(parts.next()).map(|v| limits.nodes = Some(v)),
+            "mate" => parse_value(parts.next()).map(|v| limits.mate = Some(v)),
+            _ => {
+                send(log, &format!("info string unknown go parameter: {}", key));
+                continue;
+            }
+        };
+        match result {
+            Ok(()) => {}
+            Err(Some(value)) => send(
+                log,
+                &format!("info string invalid {} value: {}", key, value),
+            ),
+            Err(None) => send(log, &format!("info string missing {} value", key)),
+        }
+    }
+    limits
+}
+
+fn parse_value<T: std::str::FromStr>(token: Option<&str>) -> Result<T, Option<&str>> {
+    let token = token.ok_or(None)?;
+    token.parse().map_err(|_| Some(token))
+}
+
+// GUIs report a negative clock once a player has flagged; treat it as empty.
+fn millis(value: i64) -> Duration {
+    Duration::from_millis(value.max(0) as u64)
+}
+
+fn handle_perft(line: &str, engine: &Engine, log: &mut Option<File>) {
+    let Some(Ok(depth)) = line.split_whitespace().nth(2).map(str::parse::<u8>) else {
+        send(log, "info string invalid perft depth");
+        return;
+    };
+    // Perft of depth 0 counts the root position itself.
+    let mut total = if depth == 0 { 1 } else { 0 };
+    for (mv, nodes) in engine.divide(depth) {
+        send(log, &format!("{}: {}", to_uci(mv), nodes));
+        total += nodes;
+    }
+    send(log, "");
+    send(log, &format!("Nodes searched: {}", total));
+}
+
 fn parse_uci_move(token: &str, legal: &[Move]) -> Option<Move> {
     if token.len() < 4 || token.len() > 5 {
         return None;
@@ -1,6 +1,9 @@
-use crate::board::{in_bounds, piece_at, Color, Piece, PieceKind, Square};
-use crate::dirs::{BISHOP_DIRS, KING_DIRS, KNIGHT_DIRS, QUEEN_DIRS, ROOK_DIRS};
-use crate::moves::{Move, MoveKind};
+use crate::bitboard::{
+    Bitboard, between, bishop_attacks, king_attacks, knight_attacks, pawn_attacks, queen_attacks,
+    rook_attacks, square_bit, square_index, squares,
+};
+use crate::board::{Board, Color, Piece, PieceKind, Square, piece_at};
+use crate::moves::{Move, MoveKind, MoveList};
 use crate::state::GameState;
 
 const PROMOTION_PIECES: [PieceKind; 4] = [
@@ -10,206 +13,430 @@ const PROMOTION_PIECES: [PieceKind; 4] = [
     PieceKind::Knight,
 ];
 
-pub fn generate_candidates(state: &GameState) -> Vec<Move> {
-    let mut moves = Vec::new();
-    for rank in 0..8 {
-        for file in 0..8 {
-            let from = (file as u8, rank as u8);
-            if let Some(piece) = piece_at(&state.board, from) {
-                if piece.color != state.side_to_move {
-                    continue;
-                }
-                match piece.kind {
-                    PieceKind::Pawn => gen_pawn_moves(state, from, &mut moves),
-                    PieceKind::Knight => gen_knight_moves(state, from, &mut moves),
-                    PieceKind::Bishop => gen_slider_moves(state, from, &mut moves, &BISHOP_DIRS),
-                    PieceKind::Rook => gen_slider_moves(state, from, &mut moves, &ROOK_DIRS),
-                    PieceKind::Queen => gen_slider_moves(state, from, &mut moves, &QUEEN_DIRS),
-                    PieceKind::King => gen_king_moves(state, from, &mut moves),
-                }
-            }
+// Every pseudo-legal move: captures and promotions first, then quiet moves.
+pub fn generate_candidates(state: &GameState) -> MoveList {
+    let mut moves = MoveList::new();
+    push_captures(state, &mut moves);
+    push_quiets(state, &mut moves);
+    moves
+}
+
+// Pseudo-legal captures, en passant and every promotion, capturing or not.
+pub fn generate_captures(state: &GameState) -> MoveList {
+    let mut moves = MoveList::new();
+    push_captures(state, &mut moves);
+    moves
+}
+
+// Pseudo-legal moves that neither capture nor promote, castling included.
+pub fn generate_quiets(state: &GameState) -> MoveList {
+    let mut moves = MoveList::new();
+    push_quiets(state, &mut moves);
+    moves
+}
+
+// Replies to check: king steps to unattacked squares and, against a single
+// checker, moves that capture it or block the line. Pins are left to the
+// legality check, so the result is still pseudo-legal.
+pub fn generate_evasions(state: &GameState) -> MoveList {
+    let mut moves = MoveList::new();
+    let us = state.side_to_move;
+    let them = us.opposite();
+    let board = &state.board;
+    let own = board.pieces_of_color(us);
+    let occupied = board.occupied();
+    let king = king_square(state, us);
+    let checkers = board.attackers(king, them, occupied);
+    debug_assert!(checkers != 0, "evasions asked for outside check");
+
+    if checkers.count_ones() < 2 {
+        let block = match squares(checkers).next() {
+            Some(checker) => between(king, checker),
+            None => 0,
+        };
+        let targets = block | checkers;
+        for from in squares(board.pieces(us, PieceKind::Pawn)) {
+            gen_pawn_captures(state, from, &mut moves);
+            gen_pawn_quiets(state, from, &mut moves);
+        }
+        moves.retain(|mv| {
+            let captured = if mv.kind == MoveKind::EnPassant {
+                square_bit((mv.to.0, mv.from.1))
+            } else {
+                square_bit(mv.to)
+            };
+            captured & checkers != 0 || square_bit(mv.to) & block != 0
+        });
+        push_piece_moves(state, &mut moves, targets & !own);
+    }
+
+    let without_king = occupied & !square_bit(king);
+    for to in squares(king_attacks(king) & !own) {
+        if board.attackers(to, them, without_king) == 0 {
+            push_move(&mut moves, king, to, MoveKind::Normal);
         }
     }
     moves
 }
 
-fn gen_pawn_moves(state: &GameState, from: Square, moves: &mut Vec<Move>) {
-    let piece = piece_at(&state.board, from).expect("missing pawn");
-    let dir: i8 = if piece.color == Color::White { 1 } else { -1 };
-    let start_rank: u8 = if piece.color == Color::White { 1 } else { 6 };
-    let last_rank: u8 = if piece.color == Color::White { 7 } else { 0 };
+fn push_captures(state: &GameState, moves: &mut MoveList) {
+    let us = state.side_to_move;
+    let board = &state.board;
+    let enemies = board.pieces_of_color(us.opposite());
+    for from in squares(board.pieces(us, PieceKind::Pawn)) {
+        gen_pawn_captures(state, from, moves);
+    }
+    push_piece_moves(state, moves, enemies);
+    for from in squares(board.pieces(us, PieceKind::King)) {
+        push_targets(moves, from, king_attacks(from) & enemies);
+    }
+}
 
-    let file = from.0 as i8;
-    let rank = from.1 as i8;
+fn push_quiets(state: &GameState, moves: &mut MoveList) {
+    let us = state.side_to_move;
+    let board = &state.board;
+    let empty = !board.occupied();
+    for from in squares(board.pieces(us, PieceKind::Pawn)) {
+        gen_pawn_quiets(state, from, moves);
+    }
+    push_piece_moves(state, moves, empty);
+    for from in squares(board.pieces(us, PieceKind::King)) {
+        push_targets(moves, from, king_attacks(from) & empty);
+        gen_castling_moves(state, from, moves);
+    }
+}
+
+// Knight, bishop, rook and queen moves landing on `targets`.
+fn push_piece_moves(state: &GameState, moves: &mut MoveList, targets: Bitboard) {
+    let us = state.side_to_move;
+    let board = &state.board;
+    let occupied = board.occupied();
+    for from in squares(board.pieces(us, PieceKind::Knight)) {
+        push_targets(moves, from, knight_attacks(from) & targets);
+    }
+    for from in squares(board.pieces(us, PieceKind::Bishop)) {
+        push_targets(moves, from, bishop_attacks(from, occupied) & targets);
+    }
+    for from in squares(board.pieces(us, PieceKind::Rook)) {
+        push_targets(moves, from, rook_attacks(from, occupied) & targets);
+    }
+    for from in squares(board.pieces(us, PieceKind::Queen)) {
+        push_targets(moves, from, queen_attacks(from, occupied) & targets);
+    }
+}
+
+fn king_square(state: &GameState, color: Color) -> Square {
+    match color {
+        Color::White => state.white_king,
+        Color::Black => state.black_king,
+    }
+}
+
+// Emits only legal moves. Checkers and pinned pieces are found up front so
+// each move can be accepted from bitboards alone; en passant is the one case
+// that needs a look at the board after the capture, since removing two pawns
+// from a rank can expose the king.
+pub fn generate_legal(state: &GameState) -> MoveList {
+    let mut moves = MoveList::new();
+    let us = state.side_to_move;
+    let them = us.opposite();
+    let board = &state.board;
+    let own = board.pieces_of_color(us);
+    let occupied = board.occupied();
+    let king = king_square(state, us);
+    let checkers = board.attackers(king, them, occupied);
 
-    let one_rank = rank + dir;
-    if in_bounds(file, one_rank) {
-        let to = (file as u8, one_rank as u8);
-        if piece_at(&state.board, to).is_none() {
-            if to.1 == last_rank {
-                add_promotion_moves(moves, from, to);
+    // With two checkers only the king can move.
+    if checkers.count_ones() < 2 {
+        // Destinations that resolve a single check: capture the checker or block.
+        let check_mask = match squares(checkers).next() {
+            Some(checker) => between(king, checker) | checkers,
+            None => !0,
+        };
+        let pin_masks = pin_masks(board, king, us, occupied);
+
+        for from in squares(board.pieces(us, PieceKind::Pawn)) {
+            gen_pawn_captures(state, from, &mut moves);
+            gen_pawn_quiets(state, from, &mut moves);
+        }
+        moves.retain(|mv| {
+            if mv.kind == MoveKind::EnPassant {
+                en_passant_is_legal(board, mv, king, them)
             } else {
-                push_move(moves, from, to, MoveKind::Normal);
-                if from.1 == start_rank {
-                    let two_rank = rank + dir * 2;
-                    let to_two = (file as u8, two_rank as u8);
-                    if in_bounds(file, two_rank) && piece_at(&state.board, to_two).is_none() {
-                        push_move(moves, from, to_two, MoveKind::Normal);
-                    }
-                }
+                check_mask & pin_masks[square_index(mv.from)] & square_bit(mv.to) != 0
+            }
+        });
+
+        for kind in [
+            PieceKind::Knight,
+            PieceKind::Bishop,
+            PieceKind::Rook,
+            PieceKind::Queen,
+        ] {
+            for from in squares(board.pieces(us, kind)) {
+                let reach = match kind {
+                    PieceKind::Knight => knight_attacks(from),
+                    PieceKind::Bishop => bishop_attacks(from, occupied),
+                    PieceKind::Rook => rook_attacks(from, occupied),
+                    _ => queen_attacks(from, occupied),
+                };
+                let allowed = !own & check_mask & pin_masks[square_index(from)];
+                push_targets(&mut moves, from, reach & allowed);
             }
         }
     }
 
-    for df in [-1, 1] {
-        let nf = file + df;
-        let nr = rank + dir;
-        if !in_bounds(nf, nr) {
-            continue;
+    let without_king = occupied & !square_bit(king);
+    for to in squares(king_attacks(king) & !own) {
+        if board.attackers(to, them, without_king) == 0 {
+            push_move(&mut moves, king, to, MoveKind::Normal);
         }
-        let to = (nf as u8, nr as u8);
-        if let Some(target) = piece_at(&state.board, to) {
-            if target.color != piece.color {
-                if to.1 == last_rank {
-                    add_promotion_moves(moves, from, to);
-                } else {
-                    push_move(moves, from, to, MoveKind::Normal);
-                }
+    }
+    if checkers == 0 {
+        let mut castles = MoveList::new();
+        gen_castling_moves(state, king, &mut castles);
+        for mv in &castles {
+            if squares(between(king, mv.to) | square_bit(mv.to))
+                .all(|sq| board.attackers(sq, them, occupied) == 0)
+            {
+                moves.push(mv);
             }
         }
     }
+    moves
+}
 
-    if let Some(ep) = state.en_passant {
-        let ep_file = ep.0 as i8;
-        let ep_rank = ep.1 as i8;
-        if ep_rank == rank + dir && (ep_file - file).abs() == 1 {
-            push_move(moves, from, ep, MoveKind::EnPassant);
+// For each square, where a piece standing on it may move without exposing
+// the king. Unpinned squares allow everything; a pinned piece stays on the
+// line between the king and the pinner, capture included.
+fn pin_masks(board: &Board, king: Square, us: Color, occupied: Bitboard) -> [Bitboard; 64] {
+    let them = us.opposite();
+    let queens = board.pieces(them, PieceKind::Queen);
+    let snipers = (rook_attacks(king, 0) & (board.pieces(them, PieceKind::Rook) | queens))
+        | (bishop_attacks(king, 0) & (board.pieces(them, PieceKind::Bishop) | queens));
+    let mut masks = [!0; 64];
+    for sniper in squares(snipers) {
+        let line = between(king, sniper);
+        let blockers = line & occupied;
+        if blockers.count_ones() == 1 && blockers & board.pieces_of_color(us) != 0 {
+            masks[blockers.trailing_zeros() as usize] = line | square_bit(sniper);
         }
     }
+    masks
 }
 
-fn gen_knight_moves(state: &GameState, from: Square, moves: &mut Vec<Move>) {
-    let piece = piece_at(&state.board, from).expect("missing knight");
-    let file = from.0 as i8;
-    let rank = from.1 as i8;
+fn en_passant_is_legal(board: &Board, mv: Move, king: Square, them: Color) -> bool {
+    let captured = square_bit((mv.to.0, mv.from.1));
+    let occupied = (board.occupied() & !square_bit(mv.from) & !captured) | square_bit(mv.to);
+    board.attackers(king, them, occupied) & !captured == 0
+}
 
-    for (df, dr) in KNIGHT_DIRS {
-        let nf = file + df;
-        let nr = rank + dr;
-        if !in_bounds(nf, nr) {
-            continue;
-        }
-        let to = (nf as u8, nr as u8);
-        add_step_move(state, piece, from, to, moves);
-    }
-}
-
-fn gen_slider_moves(
-    state: &GameState,
-    from: Square,
-    moves: &mut Vec<Move>,
-    dirs: &[(i8, i8)],
-) {
-    let piece = piece_at(&state.board, from).expect("missing slider");
-    let file = from.0 as i8;
-    let rank = from.1 as i8;
-
-    for (df, dr) in dirs {
-        let mut nf = file + df;
-        let mut nr = rank + dr;
-        while in_bounds(nf, nr) {
-            let to = (nf as u8, nr as u8);
-            if let Some(target) = piece_at(&state.board, to) {
-                if target.color != piece.color {
-                    push_move(moves, from, to, MoveKind::Normal);
-                }
-                break;
-            } else {
-                push_move(moves, from, to, MoveKind::Normal);
-            }
-            nf += df;
-            nr += dr;
+// Captures, en passant, and pushes onto the last rank.
+fn gen_pawn_captures(state: &GameState, from: Square, moves: &mut MoveList) {
+    let color = state.side_to_move;
+    let last_rank: u8 = if color == Color::White { 7 } else { 0 };
+
+    let attacks = pawn_attacks(color, from);
+    for to in squares(attacks & state.board.pieces_of_color(color.opposite())) {
+        if to.1 == last_rank {
+            add_promotion_moves(moves, from, to);
+        } else {
+            push_move(moves, from, to, MoveKind::Normal);
         }
     }
+
+    if let Some(ep) = state.en_passant
+        && attacks & square_bit(ep) != 0
+    {
+        push_move(moves, from, ep, MoveKind::EnPassant);
+    }
+
+    if let Some(to) = pawn_push(color, from)
+        && to.1 == last_rank
+        && piece_at(&state.board, to).is_none()
+    {
+        add_promotion_moves(moves, from, to);
+    }
 }
 
-fn gen_king_moves(state: &GameState, from: Square, moves: &mut Vec<Move>) {
-    let piece = piece_at(&state.board, from).expect("missing king");
-    let file = from.0 as i8;
-    let rank = from.1 as i8;
-
-    for (df, dr) in KING_DIRS {
-        let nf = file + df;
-        let nr = rank + dr;
-        if !in_bounds(nf, nr) {
-            continue;
-        }
-        let to = (nf as u8, nr as u8);
-        add_step_move(state, piece, from, to, moves);
+// Single and double pushes that stop short of the last rank.
+fn gen_pawn_quiets(state: &GameState, from: Square, moves: &mut MoveList) {
+    let color = state.side_to_move;
+    let start_rank: u8 = if color == Color::White { 1 } else { 6 };
+    let last_rank: u8 = if color == Color::White { 7 } else { 0 };
+
+    let Some(to) = pawn_push(color, from) else {
+        return;
+    };
+    if to.1 == last_rank || piece_at(&state.board, to).is_some() {
+        return;
     }
+    push_move(moves, from, to, MoveKind::Normal);
+    if from.1 == start_rank
+        && let Some(to_two) = pawn_push(color, to)
+        && piece_at(&state.board, to_two).is_none()
+    {
+        push_move(moves, from, to_two, MoveKind::Normal);
+    }
+}
 
+fn pawn_push(color: Color, from: Square) -> Option<Square> {
+    let dir: i8 = if color == Color::White { 1 } else { -1 };
+    let rank = from.1 as i8 + dir;
+    (0..8).contains(&rank).then_some((from.0, rank as u8))
+}
+
+// Castling candidates with the path clear and the rook in place; whether the
+// king passes through check is left to the caller.
+fn gen_castling_moves(state: &GameState, from: Square, moves: &mut MoveList) {
+    let piece = piece_at(&state.board, from).expect("missing king");
     let rook = Piece {
         color: piece.color,
         kind: PieceKind::Rook,
     };
     match piece.color {
         Color::White => {
-            if from == (4, 0) && state.castling.white_kingside {
-                if piece_at(&state.board, (5, 0)).is_none()
-                    && piece_at(&state.board, (6, 0)).is_none()
-                    && piece_at(&state.board, (7, 0)) == Some(rook)
-                {
-                    push_move(moves, from, (6, 0), MoveKind::CastleKingside);
-                }
+            if from == (4, 0)
+                && state.castling.white_kingside
+                && piece_at(&state.board, (5, 0)).is_none()
+                && piece_at(&state.board, (6, 0)).is_none()
+                && piece_at(&state.board, (7, 0)) == Some(rook)
+            {
+                push_move(moves, from, (6, 0), MoveKind::CastleKingside);
             }
-            if from == (4, 0) && state.castling.white_queenside {
-                if piece_at(&state.board, (1, 0)).is_none()
-                    && piece_at(&state.board, (2, 0)).is_none()
-                    && piece_at(&state.board, (3, 0)).is_none()
-                    && piece_at(&state.board, (0, 0)) == Some(rook)
-                {
-                    push_move(moves, from, (2, 0), MoveKind::CastleQueenside);
-                }
+            if from == (4, 0)
+                && state.castling.white_queenside
+                && piece_at(&state.board, (1, 0)).is_none()
+                && piece_at(&state.board, (2, 0)).is_none()
+                && piece_at(&state.board, (3, 0)).is_none()
+                && piece_at(&state.board, (0, 0)) == Some(rook)
+            {
+                push_move(moves, from, (2, 0), MoveKind::CastleQueenside);
             }
         }
         Color::Black => {
-            if from == (4, 7) && state.castling.black_kingside {
-                if piece_at(&state.board, (5, 7)).is_none()
-                    && piece_at(&state.board, (6, 7)).is_none()
-                    && piece_at(&state.board, (7, 7)) == Some(rook)
-                {
-                    push_move(moves, from, (6, 7), MoveKind::CastleKingside);
-                }
+            if from == (4, 7)
+                && state.castling.black_kingside
+                && piece_at(&state.board, (5, 7)).is_none()
+                && piece_at(&state.board, (6, 7)).is_none()
+                && piece_at(&state.board, (7, 7)) == Some(rook)
+            {
+                push_move(moves, from, (6, 7), MoveKind::CastleKingside);
             }
-            if from == (4, 7) && state.castling.black_queenside {
-                if piece_at(&state.board, (1, 7)).is_none()
-                    && piece_at(&state.board, (2, 7)).is_none()
-                    && piece_at(&state.board, (3, 7)).is_none()
-                    && piece_at(&state.board, (0, 7)) == Some(rook)
-                {
-                    push_move(moves, from, (2, 7), MoveKind::CastleQueenside);
-                }
+            if from == (4, 7)
+                && state.castling.black_queenside
+                && piece_at(&state.board, (1, 7)).is_none()
+                && piece_at(&state.board, (2, 7)).is_none()
+                && piece_at(&state.board, (3, 7)).is_none()
+                && piece_at(&state.board, (0, 7)) == Some(rook)
+            {
+                push_move(moves, from, (2, 7), MoveKind::CastleQueenside);
             }
         }
     }
 }
 
-fn add_step_move(state: &GameState, piece: Piece, from: Square, to: Square, moves: &mut Vec<Move>) {
-    if let Some(target) = piece_at(&state.board, to) {
-        if target.color != piece.color {
-            push_move(moves, from, to, MoveKind::Normal);
-        }
-    } else {
+fn push_targets(moves: &mut MoveList, from: Square, targets: Bitboard) {
+    for to in squares(targets) {
         push_move(moves, from, to, MoveKind::Normal);
     }
 }
 
-fn add_promotion_moves(moves: &mut Vec<Move>, from: Square, to: Square) {
+fn add_promotion_moves(moves: &mut MoveList, from: Square, to: Square) {
     for promo in PROMOTION_PIECES {
         push_move(moves, from, to, MoveKind::Promotion(promo));
     }
 }
 
-fn push_move(moves: &mut Vec<Move>, from: Square, to: Square, kind: MoveKind) {
+fn push_move(moves: &mut MoveList, from: Square, to: Square, kind: MoveKind) {
     moves.push(Move { from, to, kind });
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rules;
+
+    fn filtered(state: &GameState) -> Vec<Move> {
+        legal_only(state, generate_candidates(state).iter())
+    }
+
+    fn legal_only(state: &GameState, moves: impl Iterator<Item = Move>) -> Vec<Move> {
+        let mut scratch = state.clone();
+        moves
+            .filter(|mv| match rules::make_move(&mut scratch, *mv) {
+                Some(record) => {
+                    rules::unmake_move(&mut scratch, &record);
+                    true
+                }
+                None => false,
+            })
+            .collect()
+    }
+
+    fn assert_agrees(state: &mut GameState, depth: u8) {
+        let mut legal = generate_legal(state).to_vec();
+        let mut expected = filtered(state);
+        let key = |mv: &Move| (mv.from, mv.to, format!("{:?}", mv.kind));
+        legal.sort_by_key(key);
+        expected.sort_by_key(key);
+        assert_eq!(legal, expected, "{}", state.to_fen());
+
+        let mut candidates = generate_candidates(state).to_vec();
+        let mut staged = generate_captures(state).to_vec();
+        staged.extend(generate_quiets(state).iter());
+        candidates.sort_by_key(key);
+        staged.sort_by_key(key);
+        assert_eq!(staged, candidates, "{}", state.to_fen());
+        for mv in generate_captures(state).iter() {
+            assert!(
+                mv.is_promotion() || rules::is_capture(state, mv),
+                "{mv:?} in {}",
+                state.to_fen()
+            );
+        }
+        if rules::is_in_check(state, state.side_to_move) {
+            let mut evasions = legal_only(state, generate_evasions(state).iter());
+            evasions.sort_by_key(key);
+            assert_eq!(evasions, expected, "{}", state.to_fen());
+        }
+        if depth == 0 {
+            return;
+        }
+        for mv in legal {
+            let record = rules::make_move(state, mv).unwrap();
+            assert_agrees(state, depth - 1);
+            rules::unmake_move(state, &record);
+        }
+    }
+
+    #[test]
+    fn legal_generator_matches_filtered_candidates() {
+        for (fen, depth) in [
+            (
+                "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+                2,
+            ),
+            (
+                "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+                2,
+            ),
+            ("8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1", 3),
+            (
+                "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+                2,
+            ),
+            (
+                "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+                2,
+            ),
+            ("8/8/8/2k5/3Pp3/8/8/4K2Q b - d3 0 1", 1),
+            ("8/8/8/K2Pp2q/8/8/8/7k w - e6 0 1", 1),
+            ("8/8/8/2k5/3Pp3/8/8/4K3 b - d3 0 1", 1),
+            ("4k3/8/8/8/1b6/8/3N4/r3K3 w - - 0 1", 2),
+        ] {
+            let mut state = GameState::from_fen(fen).unwrap();
+            assert_agrees(&mut state, depth);
+        }
+    }
+}
@@ -1,4 +1,6 @@
-use crate::board::{PieceKind, Square};
+use crate::bitboard::{index_square, square_index};
+use crate::board::{Piece, PieceKind, Square};
+use crate::state::CastlingRights;
 
 #[derive(Copy, Clone, Eq, PartialEq, Debug)]
 pub enum MoveKind {
@@ -15,3 +17,332 @@ pub struct Move {
     pub to: Square,
     pub kind: MoveKind,
 }
+
+impl Move {
+    pub fn is_castle(self) -> bool {
+        matches!(
+            self.kind,
+            MoveKind::CastleKingside | MoveKind::CastleQueenside
+        )
+    }
+
+    pub fn is_promotion(self) -> bool {
+        matches!(self.kind, MoveKind::Promotion(_))
+    }
+}
+
+// A `Move` in 16 bits: origin in bits 0-5, destination in bits 6-11 and the
+// kind in bits 12-15. Every `Move` round-trips through it unchanged.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub struct PackedMove(u16);
+
+const FLAG_NORMAL: u16 = 0;
+const FLAG_EN_PASSANT: u16 = 1;
+const FLAG_CASTLE_KINGSIDE: u16 = 2;
+const FLAG_CASTLE_QUEENSIDE: u16 = 3;
+// Promotion flags follow in `PieceKind` order, starting here.
+const FLAG_PROMOTION: u16 = 4;
+
+const PROMOTION_KINDS: [PieceKind; 6] = [
+    PieceKind::Pawn,
+    PieceKind::Knight,
+    PieceKind::Bishop,
+    PieceKind::Rook,
+    PieceKind::Queen,
+    PieceKind::King,
+];
+
+impl PackedMove {
+    // `None` for a flag no move encodes, so `kind` never sees one.
+    pub const fn from_raw(raw: u16) -> Option<Self> {
+        if raw >> 12 < FLAG_PROMOTION + PROMOTION_KINDS.len() as u16 {
+            Some(Self(raw))
+        } else {
+            None
+        }
+    }
+
+    pub const fn raw(self) -> u16 {
+        self.0
+    }
+
+    pub fn from_square(self) -> Square {
+        index_square((self.0 & 0x3f) as usize)
+    }
+
+    pub fn to_square(self) -> Square {
+        index_square((self.0 >> 6 & 0x3f) as usize)
+    }
+
+    pub fn kind(self) -> MoveKind {
+        match self.0 >> 12 {
+            FLAG_NORMAL => MoveKind::Normal,
+            FLAG_EN_PASSANT => MoveKind::EnPassant,
+            FLAG_CASTLE_KINGSIDE => MoveKind::CastleKingside,
+            FLAG_CASTLE_QUEENSIDE => MoveKind::CastleQueenside,
+            flag => MoveKind::Promotion(PROMOTION_KINDS[(flag - FLAG_PROMOTION) as usize]),
+        }
+    }
+}
+
+impl From<Move> for PackedMove {
+    fn from(mv: Move) -> Self {
+        let flag = match mv.kind {
+            MoveKind::Normal => FLAG_NORMAL,
+            MoveKind::EnPassant => FLAG_EN_PASSANT,
+            MoveKind::CastleKingside => FLAG_CASTLE_KINGSIDE,
+            MoveKind::CastleQueenside => FLAG_CASTLE_QUEENSIDE,
+            MoveKind::Promotion(kind) => FLAG_PROMOTION + kind as u16,
+        };
+        Self(square_index(mv.from) as u16 | (square_index(mv.to) as u16) << 6 | flag << 12)
+    }
+}
+
+impl From<PackedMove> for Move {
+    fn from(packed: PackedMove) -> Self {
+        Move {
+            from: packed.from_square(),
+            to: packed.to_square(),
+            kind: packed.kind(),
+        }
+    }
+}
+
+pub const MAX_MOVES: usize = 256;
+
+// Fixed-capacity move buffer that lives on the stack. No legal position has
+// more than 218 moves, so pushing past `MAX_MOVES` is a generator bug.
+#[derive(Clone)]
+pub struct MoveList {
+    moves: [PackedMove; MAX_MOVES],
+    len: usize,
+}
+
+impl MoveList {
+    pub fn new() -> Self {
+        Self {
+            moves: [PackedMove(0); MAX_MOVES],
+            len: 0,
+        }
+    }
+
+    pub fn push(&mut self, mv: Move) {
+        self.moves[self.len] = mv.into();
+        self.len += 1;
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn get(&self, index: usize) -> Option<Move> {
+        self.as_slice().get(index).map(|&packed| packed.into())
+    }
+
+    pub fn contains(&self, mv: Move) -> bool {
+        self.as_slice().contains(&mv.into())
+    }
+
+    pub fn as_slice(&self) -> &[PackedMove] {
+        &self.moves[..self.len]
+    }
+
+    pub fn iter(&self) -> Iter<'_> {
+        Iter(self.as_slice().iter())
+    }
+
+    pub fn truncate(&mut self, len: usize) {
+        self.len = self.len.min(len);
+    }
+
+    pub fn retain(&mut self, mut keep: impl FnMut(Move) -> bool) {
+        let mut kept = 0;
+        for i in 0..self.len {
+            let packed = self.moves[i];
+            if keep(packed.into()) {
+                self.moves[kept] = packed;
+                kept += 1;
+            }
+        }
+        self.len = kept;
+    }
+
+    // Stable insertion sort; lists are short and this never allocates. Each
+    // key is computed once, since callers' keys can be expensive.
+    pub fn sort_by_key<K: Ord + Copy + Default>(&mut self, mut key: impl FnMut(Move) -> K) {
+        let mut keyed = [(K::default(), PackedMove(0)); MAX_MOVES];
+        for (slot, &packed) in keyed.iter_mut().zip(self.as_slice()) {
+            *slot = (key(packed.into()), packed);
+        }
+        for i in 1..self.len {
+            let entry = keyed[i];
+            let mut j = i;
+            while j > 0 && keyed[j - 1].0 > entry.0 {
+                keyed[j] = keyed[j - 1];
+                j -= 1;
+            }
+            keyed[j] = entry;
+        }
+        for (packed, (_, sorted)) in self.moves.iter_mut().zip(&keyed[..self.len]) {
+            *packed = *sorted;
+        }
+    }
+
+    pub fn to_vec(&self) -> Vec<Move> {
+        self.iter().collect()
+    }
+}
+
+impl Default for MoveList {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl std::fmt::Debug for MoveList {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+pub struct Iter<'a>(std::slice::Iter<'a, PackedMove>);
+
+impl Iterator for Iter<'_> {
+    type Item = Move;
+
+    fn next(&mut self) -> Option<Move> {
+        self.0.next().map(|&packed| packed.into())
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
+
+impl<'a> IntoIterator for &'a MoveList {
+    type Item = Move;
+    type IntoIter = Iter<'a>;
+
+    fn into_iter(self) -> Iter<'a> {
+        self.iter()
+    }
+}
+
+pub struct IntoIter {
+    list: MoveList,
+    next: usize,
+}
+
+impl Iterator for IntoIter {
+    type Item = Move;
+
+    fn next(&mut self) -> Option<Move> {
+        let mv = self.list.get(self.next)?;
+        self.next += 1;
+        Some(mv)
+    }
+}
+
+impl IntoIterator for MoveList {
+    type Item = Move;
+    type IntoIter = IntoIter;
+
+    fn into_iter(self) -> IntoIter {
+        IntoIter {
+            list: self,
+            next: 0,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct MoveRecord {
+    pub mv: Move,
+    pub captured: Option<Piece>,
+    pub prior_castling: CastlingRights,
+    pub prior_en_passant: Option<Square>,
+    pub prior_halfmove_clock: u32,
+    pub prior_zobrist: u64,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn packed_moves_round_trip_every_kind() {
+        let mut kinds = vec![
+            MoveKind::Normal,
+            MoveKind::EnPassant,
+            MoveKind::CastleKingside,
+            MoveKind::CastleQueenside,
+        ];
+        kinds.extend(PROMOTION_KINDS.map(MoveKind::Promotion));
+        for kind in kinds {
+            for from in 0..64 {
+                for to in [0, 7, 27, 56, 63] {
+                    let mv = Move {
+                        from: index_square(from),
+                        to: index_square(to),
+                        kind,
+                    };
+                    let packed = PackedMove::from(mv);
+                    assert_eq!(Move::from(packed), mv);
+                    assert_eq!(PackedMove::from_raw(packed.raw()), Some(packed));
+                }
+            }
+        }
+        assert_eq!(std::mem::size_of::<PackedMove>(), 2);
+    }
+
+    #[test]
+    fn raw_values_with_unknown_flags_are_rejected() {
+        for raw in 0..=u16::MAX {
+            match PackedMove::from_raw(raw) {
+                Some(packed) => assert_eq!(PackedMove::from(Move::from(packed)), packed),
+                None => assert!(raw >> 12 >= 10),
+            }
+        }
+    }
+
+    #[test]
+    fn move_list_sorts_stably_and_retains() {
+        let mut list = MoveList::new();
+        for (file, kind) in [
+            (0, MoveKind::Normal),
+            (1, MoveKind::Promotion(PieceKind::Queen)),
+            (2, MoveKind::Normal),
+            (3, MoveKind::Promotion(PieceKind::Knight)),
+        ] {
+            list.push(Move {
+                from: (file, 6),
+                to: (file, 7),
+                kind,
+            });
+        }
+        let mut calls = 0;
+        list.sort_by_key(|mv| {
+            calls += 1;
+            !matches!(mv.kind, MoveKind::Promotion(_))
+        });
+        assert_eq!(calls, 4);
+        let files: Vec<u8> = list.iter().map(|mv| mv.from.0).collect();
+        assert_eq!(files, vec![1, 3, 0, 2]);
+
+        list.retain(|mv| mv.from.0 != 3);
+        assert_eq!(list.len(), 3);
+        let mut truncated = list.clone();
+        truncated.truncate(1);
+        assert_eq!(truncated.to_vec(), vec![list.get(0).unwrap()]);
+        assert!(!list.contains(Move {
+            from: (3, 6),
+            to: (3, 7),
+            kind: MoveKind::Promotion(PieceKind::Knight),
+        }));
+        assert_eq!(list.into_iter().count(), 3);
+    }
+}
@@ -1,23 +1,74 @@
-use crate::board::{in_bounds, piece_at, set_piece, Color, Piece, PieceKind, Square};
-use crate::dirs::{BISHOP_DIRS, KING_DIRS, KNIGHT_DIRS, ROOK_DIRS};
+use crate::bitboard::{
+    Bitboard, DARK_SQUARES, LIGHT_SQUARES, bishop_attacks, king_attacks, king_set_attacks,
+    knight_attacks, pawn_attacks, pawn_set_attacks, queen_attacks, rook_attacks, square_bit,
+    square_index, squares,
+};
+use crate::board::{piece_at, set_piece, Color, Piece, PieceKind, Square};
 use crate::movegen;
-use crate::moves::{Move, MoveKind};
+use crate::moves::{Move, MoveKind, MoveList, MoveRecord};
 use crate::state::{CastlingRights, GameState};
+use crate::zobrist;
 
-pub fn legal_moves(state: &GameState) -> Vec<Move> {
-    movegen::generate_candidates(state)
+pub fn legal_moves(state: &GameState) -> MoveList {
+    movegen::generate_legal(state)
+}
+
+pub fn legal_move_states(state: &GameState) -> Vec<(Move, GameState)> {
+    movegen::generate_legal(state)
         .into_iter()
-        .filter(|mv| try_apply_legal(state, *mv).is_some())
+        .map(|mv| {
+            let mut next = state.clone();
+            apply_move_unchecked(&mut next, mv);
+            (mv, next)
+        })
         .collect()
 }
 
-pub fn legal_move_states(state: &GameState) -> Vec<(Move, GameState)> {
-    movegen::generate_candidates(state)
+// Counts leaf nodes of the legal move tree, the standard check of a move
+// generator against published totals.
+pub fn perft(state: &GameState, depth: u8) -> u64 {
+    if depth == 0 {
+        return 1;
+    }
+    let mut scratch = state.clone();
+    perft_in_place(&mut scratch, depth)
+}
+
+// Perft split by root move, for narrowing a mismatch down to one line. At
+// depth 0 no move is played, so there is nothing to split.
+pub fn divide(state: &GameState, depth: u8) -> Vec<(Move, u64)> {
+    if depth == 0 {
+        return Vec::new();
+    }
+    let mut scratch = state.clone();
+    legal_moves(state)
         .into_iter()
-        .filter_map(|mv| try_apply_legal(state, mv).map(|next| (mv, next)))
+        .map(|mv| {
+            let record = apply_move_unchecked(&mut scratch, mv);
+            let nodes = match depth {
+                1 => 1,
+                _ => perft_in_place(&mut scratch, depth - 1),
+            };
+            unmake_move(&mut scratch, &record);
+            (mv, nodes)
+        })
         .collect()
 }
 
+fn perft_in_place(state: &mut GameState, depth: u8) -> u64 {
+    let moves = legal_moves(state);
+    if depth == 1 {
+        return moves.len() as u64;
+    }
+    let mut nodes = 0;
+    for mv in moves {
+        let record = apply_move_unchecked(state, mv);
+        nodes += perft_in_place(state, depth - 1);
+        unmake_move(state, &record);
+    }
+    nodes
+}
+
 pub fn is_checkmate(state: &GameState) -> bool {
     is_in_check(state, state.side_to_move) && legal_moves(state).is_empty()
 }
@@ -26,13 +77,283 @@ pub fn is_stalemate(state: &GameState) -> bool {
     !is_in_check(state, state.side_to_move) && legal_moves(state).is_empty()
 }
 
+pub fn can_claim_fifty_move_draw(state: &GameState) -> bool {
+    state.halfmove_clock >= 100 && !is_checkmate(state)
+}
+
+pub fn is_seventy_five_move_draw(state: &GameState) -> bool {
+    state.halfmove_clock >= 150 && !is_checkmate(state)
+}
+
+pub fn is_insufficient_material(state: &GameState) -> bool {
+    let board = &state.board;
+    let heavy_or_pawn = board.pieces_of_kind(PieceKind::Pawn)
+        | board.pieces_of_kind(PieceKind::Rook)
+        | board.pieces_of_kind(PieceKind::Queen);
+    if heavy_or_pawn != 0 {
+        return false;
+    }
+    let knights = board.pieces_of_kind(PieceKind::Knight);
+    let bishops = board.pieces_of_kind(PieceKind::Bishop);
+    if (knights | bishops).count_ones() <= 1 {
+        return true;
+    }
+    knights == 0 && (bishops & LIGHT_SQUARES == 0 || bishops & DARK_SQUARES == 0)
+}
+
+// `color` has nothing left to mate with by any series of legal moves: a bare
+// king, or a board that is insufficient material for both sides.
+pub fn cannot_checkmate(state: &GameState, color: Color) -> bool {
+    let board = &state.board;
+    let ours = board.pieces_of_color(color) & !board.pieces_of_kind(PieceKind::King);
+    ours == 0 || is_insufficient_material(state)
+}
+
+pub fn is_dead_position(state: &GameState) -> bool {
+    is_insufficient_material(state) || is_locked_pawn_position(state)
+}
+
+// Only kings and pawns, every pawn frozen, and neither king able to reach an
+// enemy pawn. Positions this misses are treated as playable.
+fn is_locked_pawn_position(state: &GameState) -> bool {
+    let board = &state.board;
+    let white = board.pieces(Color::White, PieceKind::Pawn);
+    let black = board.pieces(Color::Black, PieceKind::Pawn);
+    let all_pawns = white | black;
+    if all_pawns == 0 || board.occupied() != all_pawns | board.pieces_of_kind(PieceKind::King) {
+        return false;
+    }
+    if state.en_passant.is_some() || is_in_check(state, state.side_to_move) {
+        return false;
+    }
+
+    // Every pawn is blocked head-on by an enemy pawn and has nothing to take.
+    if white << 8 & !black != 0 || black >> 8 & !white != 0 {
+        return false;
+    }
+    if pawn_set_attacks(Color::White, white) & board.pieces_of_color(Color::Black) != 0
+        || pawn_set_attacks(Color::Black, black) & board.pieces_of_color(Color::White) != 0
+    {
+        return false;
+    }
+
+    [Color::White, Color::Black].into_iter().all(|color| {
+        let enemy_pawns = board.pieces(color.opposite(), PieceKind::Pawn);
+        king_set_attacks(king_region(state, color)) & enemy_pawns == 0
+    })
+}
+
+// Squares the king of `color` can walk to without stepping onto a pawn or a
+// square an enemy pawn guards.
+fn king_region(state: &GameState, color: Color) -> Bitboard {
+    let board = &state.board;
+    let enemy = color.opposite();
+    let blocked = board.pieces_of_kind(PieceKind::Pawn)
+        | pawn_set_attacks(enemy, board.pieces(enemy, PieceKind::Pawn));
+    let start = match color {
+        Color::White => state.white_king,
+        Color::Black => state.black_king,
+    };
+    let mut region = square_bit(start);
+    loop {
+        let grown = region | king_set_attacks(region) & !blocked;
+        if grown == region {
+            return region;
+        }
+        region = grown;
+    }
+}
+
+fn is_attacked_by_pawn(state: &GameState, square: Square, by_color: Color) -> bool {
+    pawn_attacks(by_color.opposite(), square) & state.board.pieces(by_color, PieceKind::Pawn) != 0
+}
+
+pub fn position_key(state: &GameState) -> u64 {
+    // FIDE only treats the en-passant square as part of the position when the
+    // capture is actually legal.
+    let ep_capture_possible = state.en_passant.is_some()
+        && movegen::generate_candidates(state)
+            .into_iter()
+            .any(|mv| mv.kind == MoveKind::EnPassant && is_move_legal(state, mv));
+    if ep_capture_possible {
+        state.zobrist
+    } else {
+        state.zobrist ^ zobrist::en_passant_key(state.en_passant)
+    }
+}
+
+pub fn moving_piece(state: &GameState, mv: Move) -> Option<Piece> {
+    piece_at(&state.board, mv.from)
+}
+
+// The piece `mv` removes from the board, which for en passant is not on the
+// destination square.
+pub fn captured_piece(state: &GameState, mv: Move) -> Option<Piece> {
+    match mv.kind {
+        MoveKind::CastleKingside | MoveKind::CastleQueenside => None,
+        MoveKind::EnPassant => Some(Piece {
+            color: state.side_to_move.opposite(),
+            kind: PieceKind::Pawn,
+        }),
+        MoveKind::Normal | MoveKind::Promotion(_) => piece_at(&state.board, mv.to),
+    }
+}
+
+pub fn is_capture(state: &GameState, mv: Move) -> bool {
+    captured_piece(state, mv).is_some()
+}
+
+// Whether `mv` leaves the opponent in check, worked out on bitboards rather
+// than by playing the move. Covers direct checks from the moved (or promoted)
+// piece, the rook in castling, and discovered checks, including the ones en
+// passant opens by clearing two squares of a rank.
+pub fn gives_check(state: &GameState, mv: Move) -> bool {
+    let Some(piece) = moving_piece(state, mv) else {
+        return false;
+    };
+    let board = &state.board;
+    let king = match piece.color {
+        Color::White => state.black_king,
+        Color::Black => state.white_king,
+    };
+
+    let mut vacated = square_bit(mv.from);
+    let mut landed = square_bit(mv.to);
+    let (checker, checker_sq) = match mv.kind {
+        MoveKind::CastleKingside | MoveKind::CastleQueenside => {
+            let (rook_from, rook_to) = castling_rook_squares(piece.color, mv.kind);
+            vacated |= square_bit(rook_from);
+            landed |= square_bit(rook_to);
+            (PieceKind::Rook, rook_to)
+        }
+        MoveKind::EnPassant => {
+            vacated |= square_bit((mv.to.0, mv.from.1));
+            (piece.kind, mv.to)
+        }
+        MoveKind::Promotion(promo) => (promo, mv.to),
+        MoveKind::Normal => (piece.kind, mv.to),
+    };
+    let occupied = (board.occupied() & !vacated) | landed;
+
+    let direct = match checker {
+        PieceKind::King => 0,
+        kind => piece_attacks(
+            Piece {
+                color: piece.color,
+                kind,
+            },
+            checker_sq,
+            occupied,
+        ),
+    };
+    if direct & square_bit(king) != 0 {
+        return true;
+    }
+
+    let queens = board.pieces(piece.color, PieceKind::Queen);
+    let diagonal = (board.pieces(piece.color, PieceKind::Bishop) | queens) & !vacated;
+    let orthogonal = (board.pieces(piece.color, PieceKind::Rook) | queens) & !vacated;
+    bishop_attacks(king, occupied) & diagonal != 0 || rook_attacks(king, occupied) & orthogonal != 0
+}
+
+// Material values for exchange evaluation, in centipawns. The king is priced
+// so that no exchange ever trades it.
+const SEE_VALUES: [i32; 6] = [100, 300, 300, 500, 900, 20_000];
+
+fn see_value(kind: PieceKind) -> i32 {
+    SEE_VALUES[kind as usize]
+}
+
+// Static exchange evaluation: the material `mv` wins for the mover once both
+// sides have made every profitable recapture on the destination square,
+// least valuable attacker first. Sliders lined up behind an attacker join in
+// as it leaves. Pins are ignored and a recapturing pawn is not promoted.
+pub fn see(state: &GameState, mv: Move) -> i32 {
+    let board = &state.board;
+    let Some(piece) = moving_piece(state, mv) else {
+        return 0;
+    };
+    let mut occupied = board.occupied() & !square_bit(mv.from);
+    let mut gain = [0; 32];
+    gain[0] = captured_piece(state, mv).map_or(0, |captured| see_value(captured.kind));
+    let mut on_square = see_value(piece.kind);
+    match mv.kind {
+        MoveKind::CastleKingside | MoveKind::CastleQueenside => return 0,
+        MoveKind::EnPassant => occupied &= !square_bit((mv.to.0, mv.from.1)),
+        MoveKind::Promotion(promo) => {
+            gain[0] += see_value(promo) - see_value(PieceKind::Pawn);
+            on_square = see_value(promo);
+        }
+        MoveKind::Normal => {}
+    }
+
+    let queens = board.pieces_of_kind(PieceKind::Queen);
+    let diagonal = board.pieces_of_kind(PieceKind::Bishop) | queens;
+    let orthogonal = board.pieces_of_kind(PieceKind::Rook) | queens;
+    let mut attackers = (board.attackers(mv.to, Color::White, occupied)
+        | board.attackers(mv.to, Color::Black, occupied))
+        & occupied;
+    let mut side = piece.color.opposite();
+    let mut depth = 0;
+    loop {
+        let ours = attackers & board.pieces_of_color(side);
+        let Some((sq, kind)) = least_valuable_attacker(state, ours) else {
+            break;
+        };
+        if kind == PieceKind::King && attackers & board.pieces_of_color(side.opposite()) != 0 {
+            break;
+        }
+        depth += 1;
+        gain[depth] = on_square - gain[depth - 1];
+        occupied &= !square_bit(sq);
+        attackers |= (bishop_attacks(mv.to, occupied) & diagonal)
+            | (rook_attacks(mv.to, occupied) & orthogonal);
+        attackers &= occupied;
+        on_square = see_value(kind);
+        side = side.opposite();
+    }
+    while depth > 0 {
+        gain[depth - 1] = -(-gain[depth - 1]).max(gain[depth]);
+        depth -= 1;
+    }
+    gain[0]
+}
+
+pub fn see_ge(state: &GameState, mv: Move, threshold: i32) -> bool {
+    see(state, mv) >= threshold
+}
+
+fn least_valuable_attacker(state: &GameState, attackers: Bitboard) -> Option<(Square, PieceKind)> {
+    [
+        PieceKind::Pawn,
+        PieceKind::Knight,
+        PieceKind::Bishop,
+        PieceKind::Rook,
+        PieceKind::Queen,
+        PieceKind::King,
+    ]
+    .into_iter()
+    .find_map(|kind| {
+        let set = attackers & state.board.pieces_of_kind(kind);
+        squares(set).next().map(|sq| (sq, kind))
+    })
+}
+
 pub fn is_move_legal(state: &GameState, mv: Move) -> bool {
     try_apply_legal(state, mv).is_some()
 }
 
 pub fn try_apply_legal(state: &GameState, mv: Move) -> Option<GameState> {
+    let mut next = state.clone();
+    make_move(&mut next, mv)?;
+    Some(next)
+}
+
+// Plays `mv` in place if it is legal, returning the record `unmake_move`
+// needs to take it back. An illegal move leaves `state` untouched.
+pub fn make_move(state: &mut GameState, mv: Move) -> Option<MoveRecord> {
     let mover = state.side_to_move;
-    if matches!(mv.kind, MoveKind::CastleKingside | MoveKind::CastleQueenside) {
+    if mv.is_castle() {
         if is_in_check(state, mover) {
             return None;
         }
@@ -41,20 +362,30 @@ pub fn try_apply_legal(state: &GameState, mv: Move) -> Option<GameState> {
         }
     }
 
-    let mut next = state.clone();
-    apply_move_unchecked(&mut next, mv);
-    if is_in_check(&next, mover) {
+    let record = apply_move_unchecked(state, mv);
+    if is_in_check(state, mover) {
+        unmake_move(state, &record);
         None
     } else {
-        Some(next)
+        Some(record)
     }
 }
 
-pub fn apply_move_unchecked(state: &mut GameState, mv: Move) {
+pub fn apply_move_unchecked(state: &mut GameState, mv: Move) -> MoveRecord {
     let from = mv.from;
     let to = mv.to;
     let moving_piece = piece_at(&state.board, from).expect("missing piece");
+    let old_castling = state.castling;
+    let mut record = MoveRecord {
+        mv,
+        captured: None,
+        prior_castling: state.castling,
+        prior_en_passant: state.en_passant,
+        prior_halfmove_clock: state.halfmove_clock,
+        prior_zobrist: state.zobrist,
+    };
 
+    state.zobrist ^= zobrist::en_passant_key(state.en_passant);
     state.en_passant = None;
 
     let mut captured_square: Option<Square> = None;
@@ -62,39 +393,21 @@ pub fn apply_move_unchecked(state: &mut GameState, mv: Move) {
 
     match mv.kind {
         MoveKind::CastleKingside | MoveKind::CastleQueenside => {
-            let (rank, rook_from_file, rook_to_file, king_to_file) = match moving_piece.color {
-                Color::White => {
-                    if mv.kind == MoveKind::CastleKingside {
-                        (0, 7, 5, 6)
-                    } else {
-                        (0, 0, 3, 2)
-                    }
-                }
-                Color::Black => {
-                    if mv.kind == MoveKind::CastleKingside {
-                        (7, 7, 5, 6)
-                    } else {
-                        (7, 0, 3, 2)
-                    }
-                }
-            };
-            let king_to = (king_to_file, rank);
-            let rook_from = (rook_from_file, rank);
-            let rook_to = (rook_to_file, rank);
+            let (rook_from, rook_to) = castling_rook_squares(moving_piece.color, mv.kind);
             let rook = piece_at(&state.board, rook_from).expect("missing rook");
 
-            set_piece(&mut state.board, from, None);
-            set_piece(&mut state.board, king_to, Some(moving_piece));
-            set_piece(&mut state.board, rook_from, None);
-            set_piece(&mut state.board, rook_to, Some(rook));
+            put_piece(state, from, None);
+            put_piece(state, to, Some(moving_piece));
+            put_piece(state, rook_from, None);
+            put_piece(state, rook_to, Some(rook));
         }
         MoveKind::EnPassant => {
             let capture_sq = (to.0, from.1);
             captured_square = Some(capture_sq);
             captured_piece = piece_at(&state.board, capture_sq);
-            set_piece(&mut state.board, capture_sq, None);
-            set_piece(&mut state.board, from, None);
-            set_piece(&mut state.board, to, Some(moving_piece));
+            put_piece(state, capture_sq, None);
+            put_piece(state, from, None);
+            put_piece(state, to, Some(moving_piece));
         }
         MoveKind::Promotion(promo) => {
             if let Some(target) = piece_at(&state.board, to) {
@@ -105,16 +418,16 @@ pub fn apply_move_unchecked(state: &mut GameState, mv: Move) {
                 color: moving_piece.color,
                 kind: promo,
             };
-            set_piece(&mut state.board, from, None);
-            set_piece(&mut state.board, to, Some(promoted));
+            put_piece(state, from, None);
+            put_piece(state, to, Some(promoted));
         }
         MoveKind::Normal => {
             if let Some(target) = piece_at(&state.board, to) {
                 captured_square = Some(to);
                 captured_piece = Some(target);
             }
-            set_piece(&mut state.board, from, None);
-            set_piece(&mut state.board, to, Some(moving_piece));
+            put_piece(state, from, None);
+            put_piece(state, to, Some(moving_piece));
         }
     }
 
@@ -130,6 +443,15 @@ pub fn apply_move_unchecked(state: &mut GameState, mv: Move) {
         update_castling_rights_on_capture(state, square, piece);
     }
 
+    if moving_piece.kind == PieceKind::Pawn || captured_piece.is_some() {
+        state.halfmove_clock = 0;
+    } else {
+        state.halfmove_clock += 1;
+    }
+    if moving_piece.color == Color::Black {
+        state.fullmove_number += 1;
+    }
+
     if moving_piece.kind == PieceKind::Pawn && mv.kind == MoveKind::Normal {
         let rank_diff = (to.1 as i8 - from.1 as i8).abs();
         if from.0 == to.0 && rank_diff == 2 {
@@ -138,7 +460,87 @@ pub fn apply_move_unchecked(state: &mut GameState, mv: Move) {
         }
     }
 
+    state.zobrist ^= zobrist::en_passant_key(state.en_passant);
+    state.zobrist ^= zobrist::castling_key(old_castling) ^ zobrist::castling_key(state.castling);
+    state.zobrist ^= zobrist::side_to_move_key();
     state.side_to_move = state.side_to_move.opposite();
+    debug_assert_eq!(state.zobrist, zobrist::hash(state));
+
+    record.captured = captured_piece;
+    record
+}
+
+pub fn unmake_move(state: &mut GameState, record: &MoveRecord) {
+    let mv = record.mv;
+    let mover = state.side_to_move.opposite();
+    let moved = piece_at(&state.board, mv.to).expect("missing moved piece");
+
+    match mv.kind {
+        MoveKind::CastleKingside | MoveKind::CastleQueenside => {
+            let (rook_from, rook_to) = castling_rook_squares(mover, mv.kind);
+            let rook = piece_at(&state.board, rook_to).expect("missing rook");
+            set_piece(&mut state.board, rook_to, None);
+            set_piece(&mut state.board, rook_from, Some(rook));
+            set_piece(&mut state.board, mv.to, None);
+            set_piece(&mut state.board, mv.from, Some(moved));
+        }
+        MoveKind::EnPassant => {
+            set_piece(&mut state.board, mv.to, None);
+            set_piece(&mut state.board, mv.from, Some(moved));
+            set_piece(&mut state.board, (mv.to.0, mv.from.1), record.captured);
+        }
+        MoveKind::Promotion(_) => {
+            let pawn = Piece {
+                color: mover,
+                kind: PieceKind::Pawn,
+            };
+            set_piece(&mut state.board, mv.to, record.captured);
+            set_piece(&mut state.board, mv.from, Some(pawn));
+        }
+        MoveKind::Normal => {
+            set_piece(&mut state.board, mv.to, record.captured);
+            set_piece(&mut state.board, mv.from, Some(moved));
+        }
+    }
+
+    if moved.kind == PieceKind::King {
+        match mover {
+            Color::White => state.white_king = mv.from,
+            Color::Black => state.black_king = mv.from,
+        }
+    }
+    if mover == Color::Black {
+        state.fullmove_number -= 1;
+    }
+
+    state.side_to_move = mover;
+    state.castling = record.prior_castling;
+    state.en_passant = record.prior_en_passant;
+    state.halfmove_clock = record.prior_halfmove_clock;
+    state.zobrist = record.prior_zobrist;
+    debug_assert_eq!(state.zobrist, zobrist::hash(state));
+}
+
+fn castling_rook_squares(color: Color, kind: MoveKind) -> (Square, Square) {
+    let rank = match color {
+        Color::White => 0,
+        Color::Black => 7,
+    };
+    if kind == MoveKind::CastleKingside {
+        ((7, rank), (5, rank))
+    } else {
+        ((0, rank), (3, rank))
+    }
+}
+
+fn put_piece(state: &mut GameState, sq: Square, piece: Option<Piece>) {
+    if let Some(old) = piece_at(&state.board, sq) {
+        state.zobrist ^= zobrist::piece_key(old, sq);
+    }
+    if let Some(new) = piece {
+        state.zobrist ^= zobrist::piece_key(new, sq);
+    }
+    set_piece(&mut state.board, sq, piece);
 }
 
 pub fn is_in_check(state: &GameState, color: Color) -> bool {
@@ -156,105 +558,66 @@ pub fn is_in_check(state: &GameState, color: Color) -> bool {
     is_square_attacked(state, king_sq, color.opposite())
 }
 
-pub fn is_square_attacked(state: &GameState, square: Square, by_color: Color) -> bool {
-    let file = square.0 as i8;
-    let rank = square.1 as i8;
-
-    let pawn_dir: i8 = if by_color == Color::White { -1 } else { 1 };
-    for df in [-1, 1] {
-        let nf = file + df;
-        let nr = rank + pawn_dir;
-        if in_bounds(nf, nr) {
-            let sq = (nf as u8, nr as u8);
-            if piece_at(&state.board, sq)
-                == Some(Piece {
-                    color: by_color,
-                    kind: PieceKind::Pawn,
-                })
-            {
-                return true;
-            }
-        }
-    }
+// Every square holding a piece of `color` that attacks `square`.
+pub fn attackers_to(state: &GameState, square: Square, color: Color) -> Bitboard {
+    state.board.attackers(square, color, state.board.occupied())
+}
 
-    for (df, dr) in KNIGHT_DIRS {
-        let nf = file + df;
-        let nr = rank + dr;
-        if in_bounds(nf, nr) {
-            let sq = (nf as u8, nr as u8);
-            if piece_at(&state.board, sq)
-                == Some(Piece {
-                    color: by_color,
-                    kind: PieceKind::Knight,
-                })
-            {
-                return true;
-            }
+// Every square `color` attacks, whether or not it holds a piece.
+pub fn attack_map(state: &GameState, color: Color) -> Bitboard {
+    let board = &state.board;
+    let occupied = board.occupied();
+    squares(board.pieces_of_color(color))
+        .map(|sq| piece_attacks(board.piece_at(sq).expect("missing piece"), sq, occupied))
+        .fold(0, |map, attacks| map | attacks)
+}
+
+// How many pieces of `color` attack each square, indexed like bitboards.
+pub fn attack_counts(state: &GameState, color: Color) -> [u8; 64] {
+    let board = &state.board;
+    let occupied = board.occupied();
+    let mut counts = [0; 64];
+    for sq in squares(board.pieces_of_color(color)) {
+        let piece = board.piece_at(sq).expect("missing piece");
+        for target in squares(piece_attacks(piece, sq, occupied)) {
+            counts[square_index(target)] += 1;
         }
     }
+    counts
+}
 
-    for (df, dr) in KING_DIRS {
-        let nf = file + df;
-        let nr = rank + dr;
-        if in_bounds(nf, nr) {
-            let sq = (nf as u8, nr as u8);
-            if piece_at(&state.board, sq)
-                == Some(Piece {
-                    color: by_color,
-                    kind: PieceKind::King,
-                })
-            {
-                return true;
-            }
-        }
+fn piece_attacks(piece: Piece, sq: Square, occupied: Bitboard) -> Bitboard {
+    match piece.kind {
+        PieceKind::Pawn => pawn_attacks(piece.color, sq),
+        PieceKind::Knight => knight_attacks(sq),
+        PieceKind::Bishop => bishop_attacks(sq, occupied),
+        PieceKind::Rook => rook_attacks(sq, occupied),
+        PieceKind::Queen => queen_attacks(sq, occupied),
+        PieceKind::King => king_attacks(sq),
     }
+}
 
-    diagonal_attacked(state, square, by_color) || orthogonal_attacked(state, square, by_color)
+pub fn is_square_attacked(state: &GameState, square: Square, by_color: Color) -> bool {
+    let board = &state.board;
+    is_attacked_by_pawn(state, square, by_color)
+        || knight_attacks(square) & board.pieces(by_color, PieceKind::Knight) != 0
+        || king_attacks(square) & board.pieces(by_color, PieceKind::King) != 0
+        || diagonal_attacked(state, square, by_color)
+        || orthogonal_attacked(state, square, by_color)
 }
 
 fn diagonal_attacked(state: &GameState, square: Square, by_color: Color) -> bool {
-    let file = square.0 as i8;
-    let rank = square.1 as i8;
-    for (df, dr) in BISHOP_DIRS {
-        let mut nf = file + df;
-        let mut nr = rank + dr;
-        while in_bounds(nf, nr) {
-            let sq = (nf as u8, nr as u8);
-            if let Some(piece) = piece_at(&state.board, sq) {
-                if piece.color == by_color
-                    && matches!(piece.kind, PieceKind::Bishop | PieceKind::Queen)
-                {
-                    return true;
-                }
-                break;
-            }
-            nf += df;
-            nr += dr;
-        }
-    }
-    false
+    let board = &state.board;
+    let sliders =
+        board.pieces(by_color, PieceKind::Bishop) | board.pieces(by_color, PieceKind::Queen);
+    bishop_attacks(square, board.occupied()) & sliders != 0
 }
 
 fn orthogonal_attacked(state: &GameState, square: Square, by_color: Color) -> bool {
-    let file = square.0 as i8;
-    let rank = square.1 as i8;
-    for (df, dr) in ROOK_DIRS {
-        let mut nf = file + df;
-        let mut nr = rank + dr;
-        while in_bounds(nf, nr) {
-            let sq = (nf as u8, nr as u8);
-            if let Some(piece) = piece_at(&state.board, sq) {
-                if piece.color == by_color && matches!(piece.kind, PieceKind::Rook | PieceKind::Queen)
-                {
-                    return true;
-                }
-                break;
-            }
-            nf += df;
-            nr += dr;
-        }
-    }
-    false
+    let board = &state.board;
+    let sliders =
+        board.pieces(by_color, PieceKind::Rook) | board.pieces(by_color, PieceKind::Queen);
+    rook_attacks(square, board.occupied()) & sliders != 0
 }
 
 fn king_passes_through_check(state: &GameState, mv: Move) -> bool {
@@ -314,11 +677,11 @@ fn clear_castling_rights(castling: &mut CastlingRights, color: Color) {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::board::set_piece;
+    use crate::board::{set_piece, Board};
 
     fn empty_state(side: Color) -> GameState {
         GameState {
-            board: [[None; 8]; 8],
+            board: Board::empty(),
             side_to_move: side,
             castling: CastlingRights {
                 white_kingside: false,
@@ -329,6 +692,9 @@ mod tests {
             en_passant: None,
             white_king: (4, 0),
             black_king: (4, 7),
+            halfmove_clock: 0,
+            fullmove_number: 1,
+            zobrist: 0,
         }
     }
 
@@ -370,6 +736,7 @@ mod tests {
             }),
         );
 
+        state.zobrist = zobrist::hash(&state);
         let moves = legal_moves(&state);
         assert!(!moves
             .iter()
@@ -414,6 +781,7 @@ mod tests {
             }),
         );
 
+        state.zobrist = zobrist::hash(&state);
         let moves = legal_moves(&state);
         assert!(!moves.iter().any(|mv| {
             mv.from == (4, 4)
@@ -459,6 +827,7 @@ mod tests {
             }),
         );
 
+        state.zobrist = zobrist::hash(&state);
         let moves = legal_moves(&state);
         let quiet_promotions = moves
             .iter()
@@ -480,4 +849,531 @@ mod tests {
         assert_eq!(quiet_promotions, 4);
         assert_eq!(capture_promotions, 4);
     }
+
+    #[test]
+    fn halfmove_clock_resets_on_pawn_move_and_capture() {
+        let mut state = GameState::from_fen("4k3/8/8/5p2/8/8/4P3/R3K2N w - - 5 10").unwrap();
+
+        apply_move_unchecked(
+            &mut state,
+            Move {
+                from: (7, 0),
+                to: (6, 2),
+                kind: MoveKind::Normal,
+            },
+        );
+        assert_eq!(state.halfmove_clock, 6);
+        assert_eq!(state.fullmove_number, 10);
+
+        apply_move_unchecked(
+            &mut state,
+            Move {
+                from: (4, 7),
+                to: (4, 6),
+                kind: MoveKind::Normal,
+            },
+        );
+        assert_eq!(state.halfmove_clock, 7);
+        assert_eq!(state.fullmove_number, 11);
+
+        apply_move_unchecked(
+            &mut state,
+            Move {
+                from: (4, 1),
+                to: (4, 3),
+                kind: MoveKind::Normal,
+            },
+        );
+        assert_eq!(state.halfmove_clock, 0);
+
+        apply_move_unchecked(
+            &mut state,
+            Move {
+                from: (4, 6),
+                to: (4, 5),
+                kind: MoveKind::Normal,
+            },
+        );
+        assert_eq!(state.halfmove_clock, 1);
+
+        apply_move_unchecked(
+            &mut state,
+            Move {
+                from: (6, 2),
+                to: (5, 4),
+                kind: MoveKind::Normal,
+            },
+        );
+        assert_eq!(state.halfmove_clock, 0);
+        assert_eq!(state.fullmove_number, 12);
+    }
+
+    #[test]
+    fn zobrist_updates_incrementally_for_every_move_kind() {
+        let fens = [
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+            "4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1",
+        ];
+        for fen in fens {
+            let state = GameState::from_fen(fen).unwrap();
+            for (_, next) in legal_move_states(&state) {
+                assert_eq!(next.zobrist, zobrist::hash(&next));
+                for (_, reply) in legal_move_states(&next) {
+                    assert_eq!(reply.zobrist, zobrist::hash(&reply));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn transposed_positions_share_zobrist() {
+        let mut a = GameState::new();
+        let mut b = GameState::new();
+        let knight_g = Move {
+            from: (6, 0),
+            to: (5, 2),
+            kind: MoveKind::Normal,
+        };
+        let knight_b = Move {
+            from: (1, 0),
+            to: (2, 2),
+            kind: MoveKind::Normal,
+        };
+        let reply_g = Move {
+            from: (6, 7),
+            to: (5, 5),
+            kind: MoveKind::Normal,
+        };
+        let reply_b = Move {
+            from: (1, 7),
+            to: (2, 5),
+            kind: MoveKind::Normal,
+        };
+        for mv in [knight_g, reply_g, knight_b, reply_b] {
+            apply_move_unchecked(&mut a, mv);
+        }
+        for mv in [knight_b, reply_b, knight_g, reply_g] {
+            apply_move_unchecked(&mut b, mv);
+        }
+        assert_eq!(a.zobrist, b.zobrist);
+        assert_ne!(a.zobrist, GameState::new().zobrist);
+    }
+
+    #[test]
+    fn position_key_ignores_uncapturable_en_passant() {
+        let with_ep = GameState::from_fen("4k3/8/8/8/4P3/8/8/4K3 b - e3 0 1").unwrap();
+        let without_ep = GameState::from_fen("4k3/8/8/8/4P3/8/8/4K3 b - - 0 1").unwrap();
+        assert_ne!(with_ep.zobrist, without_ep.zobrist);
+        assert_eq!(position_key(&with_ep), position_key(&without_ep));
+    }
+
+    #[test]
+    fn position_key_keeps_capturable_en_passant() {
+        let with_ep = GameState::from_fen("4k3/8/8/8/3pP3/8/8/4K3 b - e3 0 1").unwrap();
+        let without_ep = GameState::from_fen("4k3/8/8/8/3pP3/8/8/4K3 b - - 0 1").unwrap();
+        assert_ne!(position_key(&with_ep), position_key(&without_ep));
+    }
+
+    #[test]
+    fn position_key_ignores_pinned_en_passant() {
+        let with_ep = GameState::from_fen("8/8/8/8/k2pP2R/8/8/4K3 b - e3 0 1").unwrap();
+        let without_ep = GameState::from_fen("8/8/8/8/k2pP2R/8/8/4K3 b - - 0 1").unwrap();
+        assert_eq!(position_key(&with_ep), position_key(&without_ep));
+    }
+
+    #[test]
+    fn move_count_draws_follow_halfmove_clock() {
+        let quiet = GameState::from_fen("4k3/8/8/8/8/8/8/R3K3 w - - 99 80").unwrap();
+        assert!(!can_claim_fifty_move_draw(&quiet));
+
+        let fifty = GameState::from_fen("4k3/8/8/8/8/8/8/R3K3 w - - 100 80").unwrap();
+        assert!(can_claim_fifty_move_draw(&fifty));
+        assert!(!is_seventy_five_move_draw(&fifty));
+
+        let seventy_five = GameState::from_fen("4k3/8/8/8/8/8/8/R3K3 w - - 150 105").unwrap();
+        assert!(can_claim_fifty_move_draw(&seventy_five));
+        assert!(is_seventy_five_move_draw(&seventy_five));
+    }
+
+    #[test]
+    fn checkmate_takes_precedence_over_move_count_draws() {
+        let mate = GameState::from_fen("R3k3/8/4K3/8/8/8/8/8 b - - 150 105").unwrap();
+        assert!(is_checkmate(&mate));
+        assert!(!can_claim_fifty_move_draw(&mate));
+        assert!(!is_seventy_five_move_draw(&mate));
+    }
+
+    #[test]
+    fn insufficient_material_cases() {
+        let insufficient = [
+            "4k3/8/8/8/8/8/8/4K3 w - - 0 1",
+            "4k3/8/8/8/8/8/8/4KN2 w - - 0 1",
+            "4k3/8/8/8/8/8/8/4KB2 b - - 0 1",
+            "4kb2/8/8/8/8/8/8/4K3 w - - 0 1",
+            "2b1k3/8/8/8/8/8/8/4KB2 w - - 0 1",
+            "4k3/8/8/8/8/8/8/B1B1K3 w - - 0 1",
+        ];
+        for fen in insufficient {
+            let state = GameState::from_fen(fen).unwrap();
+            assert!(is_insufficient_material(&state), "{}", fen);
+            assert!(is_dead_position(&state), "{}", fen);
+        }
+
+        let sufficient = [
+            "4k3/8/8/8/8/8/4P3/4K3 w - - 0 1",
+            "4k3/8/8/8/8/8/8/4KR2 w - - 0 1",
+            "4k3/8/8/8/8/8/8/3NKN2 w - - 0 1",
+            "4kn2/8/8/8/8/8/8/4KN2 w - - 0 1",
+            "3bk3/8/8/8/8/8/8/4KB2 w - - 0 1",
+            "4k3/8/8/8/8/8/8/2B1KB2 w - - 0 1",
+            "4kn2/8/8/8/8/8/8/4KB2 w - - 0 1",
+        ];
+        for fen in sufficient {
+            let state = GameState::from_fen(fen).unwrap();
+            assert!(!is_insufficient_material(&state), "{}", fen);
+        }
+    }
+
+    #[test]
+    fn locked_pawn_chains_are_dead() {
+        let fortress = GameState::from_fen("8/3k4/8/p1p1p1p1/P1P1P1P1/8/3K4/8 w - - 0 1").unwrap();
+        assert!(!is_insufficient_material(&fortress));
+        assert!(is_dead_position(&fortress));
+
+        let chain =
+            GameState::from_fen("8/3k4/1p1p1p1p/pPpPpPpP/P1P1P1P1/8/3K4/8 b - - 0 1").unwrap();
+        assert!(is_dead_position(&chain));
+    }
+
+    #[test]
+    fn locked_pawns_with_reachable_pawn_are_not_dead() {
+        let open = GameState::from_fen("8/3k4/8/p7/P7/8/3K4/8 w - - 0 1").unwrap();
+        assert!(!is_dead_position(&open));
+
+        let mut with_knight =
+            GameState::from_fen("8/3k4/8/p1p1p1p1/P1P1P1P1/8/3K4/8 w - - 0 1").unwrap();
+        set_piece(
+            &mut with_knight.board,
+            (0, 0),
+            Some(Piece {
+                color: Color::White,
+                kind: PieceKind::Knight,
+            }),
+        );
+        assert!(!is_dead_position(&with_knight));
+
+        let unblocked = GameState::from_fen("8/3k4/8/p1p1p1p1/PP2P1P1/8/3K4/8 w - - 0 1").unwrap();
+        assert!(!is_dead_position(&unblocked));
+    }
+
+    #[test]
+    fn undo_restores_state_exactly() {
+        let fens = [
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 3 17",
+            "r2q1rk1/pP1p2pp/Q4n2/bbp1p3/Np6/1B3NBn/pPPP1PPP/R3K2R b KQ - 0 1",
+            "4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1",
+        ];
+        for fen in fens {
+            let state = GameState::from_fen(fen).unwrap();
+            for mv in legal_moves(&state) {
+                let mut next = state.clone();
+                let record = apply_move_unchecked(&mut next, mv);
+                for reply in legal_moves(&next) {
+                    let mut after = next.clone();
+                    let reply_record = apply_move_unchecked(&mut after, reply);
+                    unmake_move(&mut after, &reply_record);
+                    assert_eq!(after, next);
+                }
+                unmake_move(&mut next, &record);
+                assert_eq!(next, state);
+            }
+        }
+    }
+
+    #[test]
+    fn divide_sums_to_perft() {
+        let state = GameState::from_fen(
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+        )
+        .unwrap();
+        assert_eq!(perft(&state, 0), 1);
+        assert!(divide(&state, 0).is_empty());
+        assert_eq!(perft(&state, 1), 48);
+        let split = divide(&state, 2);
+        assert_eq!(split.len(), 48);
+        assert_eq!(split.iter().map(|(_, nodes)| nodes).sum::<u64>(), 2039);
+    }
+
+    #[test]
+    fn classifies_captures_and_moving_pieces() {
+        let state = GameState::from_fen("r3k2r/8/8/3pP3/8/8/1p6/R3K2R w KQkq d6 0 1").unwrap();
+        let ep = Move {
+            from: (4, 4),
+            to: (3, 5),
+            kind: MoveKind::EnPassant,
+        };
+        let black_pawn = Piece {
+            color: Color::Black,
+            kind: PieceKind::Pawn,
+        };
+        assert!(is_capture(&state, ep));
+        assert_eq!(captured_piece(&state, ep), Some(black_pawn));
+        assert_eq!(
+            moving_piece(&state, ep),
+            Some(Piece {
+                color: Color::White,
+                kind: PieceKind::Pawn,
+            })
+        );
+
+        let rook_takes = Move {
+            from: (0, 0),
+            to: (0, 7),
+            kind: MoveKind::Normal,
+        };
+        assert_eq!(
+            captured_piece(&state, rook_takes).map(|piece| piece.kind),
+            Some(PieceKind::Rook)
+        );
+
+        let castle = Move {
+            from: (4, 0),
+            to: (6, 0),
+            kind: MoveKind::CastleKingside,
+        };
+        assert!(castle.is_castle() && !castle.is_promotion());
+        assert!(!is_capture(&state, castle));
+        assert!(!is_capture(
+            &state,
+            Move {
+                from: (0, 0),
+                to: (0, 3),
+                kind: MoveKind::Normal,
+            }
+        ));
+    }
+
+    #[test]
+    fn gives_check_matches_playing_the_move() {
+        for fen in [
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+            "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+            "5k2/8/8/8/8/8/8/4K2R w K - 0 1",
+            "8/8/8/1k1pP2R/8/8/8/4K3 w - d6 0 1",
+            "8/8/8/8/k2pP2Q/8/8/3K4 b - e3 0 1",
+        ] {
+            let state = GameState::from_fen(fen).unwrap();
+            for mv in legal_moves(&state).iter() {
+                let next = try_apply_legal(&state, mv).unwrap();
+                assert_eq!(
+                    gives_check(&state, mv),
+                    is_in_check(&next, next.side_to_move),
+                    "{mv:?} in {fen}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn see_scores_exchanges() {
+        let normal = |from, to| Move {
+            from,
+            to,
+            kind: MoveKind::Normal,
+        };
+
+        let state = GameState::from_fen("1k1r4/1pp4p/p7/4p3/8/P5P1/1PP4P/2K1R3 w - - 0 1").unwrap();
+        assert_eq!(see(&state, normal((4, 0), (4, 4))), 100);
+
+        let state = GameState::from_fen("4k3/8/3p4/4p3/8/8/8/4RK2 w - - 0 1").unwrap();
+        assert_eq!(see(&state, normal((4, 0), (4, 4))), -400);
+
+        let state = GameState::from_fen("1k1r3q/1ppn3p/p4b2/4p3/8/P2N2P1/1PP1R1BP/2K1Q3 w - - 0 1")
+            .unwrap();
+        assert!(see(&state, normal((3, 2), (4, 4))) < 0);
+
+        let quiet_into_pawn = GameState::from_fen("4k3/8/3p4/8/8/8/8/2Q1K3 w - - 0 1").unwrap();
+        assert_eq!(see(&quiet_into_pawn, normal((2, 0), (2, 4))), -900);
+    }
+
+    #[test]
+    fn see_counts_x_ray_attackers() {
+        let state = GameState::from_fen("4r1k1/8/8/4p3/8/8/4R3/4R1K1 w - - 0 1").unwrap();
+        let capture = Move {
+            from: (4, 1),
+            to: (4, 4),
+            kind: MoveKind::Normal,
+        };
+        assert_eq!(see(&state, capture), 100);
+        assert!(see_ge(&state, capture, 100));
+        assert!(!see_ge(&state, capture, 101));
+
+        let single = GameState::from_fen("4r1k1/8/8/4p3/8/8/4R3/6K1 w - - 0 1").unwrap();
+        assert_eq!(see(&single, capture), -400);
+    }
+
+    // Plays the exchange out recursively, each side capturing with its least
+    // valuable attacker or stopping, whichever is better for it.
+    fn see_reference(state: &GameState, mv: Move) -> i32 {
+        fn exchange(
+            state: &GameState,
+            sq: Square,
+            occupied: Bitboard,
+            side: Color,
+            on_square: i32,
+        ) -> i32 {
+            let board = &state.board;
+            let attackers = (board.attackers(sq, Color::White, occupied)
+                | board.attackers(sq, Color::Black, occupied))
+                & occupied;
+            let Some((from, kind)) =
+                least_valuable_attacker(state, attackers & board.pieces_of_color(side))
+            else {
+                return 0;
+            };
+            if kind == PieceKind::King && attackers & board.pieces_of_color(side.opposite()) != 0 {
+                return 0;
+            }
+            let reply = exchange(
+                state,
+                sq,
+                occupied & !square_bit(from),
+                side.opposite(),
+                see_value(kind),
+            );
+            (on_square - reply).max(0)
+        }
+
+        let piece = moving_piece(state, mv).unwrap();
+        let mut occupied = state.board.occupied() & !square_bit(mv.from);
+        let mut gain = captured_piece(state, mv).map_or(0, |captured| see_value(captured.kind));
+        let mut on_square = see_value(piece.kind);
+        match mv.kind {
+            MoveKind::EnPassant => occupied &= !square_bit((mv.to.0, mv.from.1)),
+            MoveKind::Promotion(promo) => {
+                gain += see_value(promo) - see_value(PieceKind::Pawn);
+                on_square = see_value(promo);
+            }
+            _ => {}
+        }
+        gain - exchange(state, mv.to, occupied, piece.color.opposite(), on_square)
+    }
+
+    #[test]
+    fn see_matches_a_recursive_exchange() {
+        // Cutting the swap list short once the sign is settled scored this
+        // as -100, though black only gets a pawn for the knight.
+        let state = GameState::from_fen(
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/P1N2Q1p/1PPBBPPP/R3K2R b KQkq - 0 1",
+        )
+        .unwrap();
+        let capture = Move {
+            from: (1, 5),
+            to: (3, 4),
+            kind: MoveKind::Normal,
+        };
+        assert_eq!(see(&state, capture), -200);
+        assert!(see_ge(&state, capture, -200));
+        assert!(!see_ge(&state, capture, -199));
+
+        let mut checked = 0;
+        for fen in [
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+            "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+            "r4rk1/1pp1qppp/p1np1n2/2b1p1B1/2B1P1b1/P1NP1N2/1PP1QPPP/R4RK1 w - - 0 10",
+        ] {
+            let root = GameState::from_fen(fen).unwrap();
+            let mut frontier = vec![root];
+            let mut states = frontier.clone();
+            for _ in 0..2 {
+                frontier = frontier
+                    .iter()
+                    .flat_map(legal_move_states)
+                    .map(|(_, state)| state)
+                    .collect();
+                states.extend(frontier.iter().cloned());
+            }
+            for state in &states {
+                for mv in &legal_moves(state) {
+                    if is_capture(state, mv) {
+                        assert_eq!(
+                            see(state, mv),
+                            see_reference(state, mv),
+                            "{:?} in {}",
+                            mv,
+                            state.to_fen()
+                        );
+                        checked += 1;
+                    }
+                }
+            }
+        }
+        assert!(checked > 10_000);
+    }
+
+    #[test]
+    fn attackers_to_lists_every_attacker() {
+        let state = GameState::from_fen("4k3/8/1qn5/4p3/3P4/8/1B6/3RK3 w - - 0 1").unwrap();
+        let black = attackers_to(&state, (3, 3), Color::Black);
+        assert_eq!(
+            black,
+            square_bit((2, 5)) | square_bit((4, 4)) | square_bit((1, 5))
+        );
+        let white = attackers_to(&state, (3, 3), Color::White);
+        assert_eq!(white, square_bit((1, 1)) | square_bit((3, 0)));
+        assert_eq!(attackers_to(&state, (7, 7), Color::White), 0);
+    }
+
+    #[test]
+    fn attack_maps_and_counts_cover_the_start_position() {
+        let state = GameState::new();
+        let map = attack_map(&state, Color::White);
+        assert_eq!(map.count_ones(), 22);
+        assert_eq!(map & (square_bit((0, 0)) | square_bit((7, 0))), 0);
+
+        let counts = attack_counts(&state, Color::White);
+        assert_eq!(counts[square_index((3, 1))], 4);
+        assert_eq!(counts[square_index((5, 2))], 3);
+        assert_eq!(counts[square_index((4, 3))], 0);
+        for (index, &count) in counts.iter().enumerate() {
+            assert_eq!(count > 0, map & (1 << index) != 0);
+        }
+        assert_eq!(attack_counts(&state, Color::Black)[square_index((2, 5))], 3);
+    }
+
+    #[test]
+    fn make_move_rejects_illegal_moves_without_changing_state() {
+        let state = GameState::from_fen("4k3/8/8/8/8/8/4r3/4K2R w K - 0 1").unwrap();
+        let mut scratch = state.clone();
+
+        let castle = Move {
+            from: (4, 0),
+            to: (6, 0),
+            kind: MoveKind::CastleKingside,
+        };
+        assert!(make_move(&mut scratch, castle).is_none());
+        assert_eq!(scratch, state);
+
+        let into_check = Move {
+            from: (4, 0),
+            to: (3, 1),
+            kind: MoveKind::Normal,
+        };
+        assert!(make_move(&mut scratch, into_check).is_none());
+        assert_eq!(scratch, state);
+
+        let capture = Move {
+            from: (4, 0),
+            to: (4, 1),
+            kind: MoveKind::Normal,
+        };
+        let record = make_move(&mut scratch, capture).unwrap();
+        assert_eq!(scratch.side_to_move, Color::Black);
+        unmake_move(&mut scratch, &record);
+        assert_eq!(scratch, state);
+    }
 }
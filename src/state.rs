@@ -1,4 +1,8 @@
-use crate::board::{Board, Color, Piece, PieceKind, Square};
+use std::fmt;
+
+use crate::board::{Board, Color, Piece, PieceKind, Square, piece_at};
+use crate::rules;
+use crate::zobrist;
 
 #[derive(Copy, Clone, Eq, PartialEq, Debug)]
 pub struct CastlingRights {
@@ -8,6 +12,12 @@ pub struct CastlingRights {
     pub black_queenside: bool,
 }
 
+impl Default for CastlingRights {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl CastlingRights {
     pub fn new() -> Self {
         Self {
@@ -19,7 +29,116 @@ impl CastlingRights {
     }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum FenErrorKind {
+    MissingField,
+    TooManyFields,
+    WrongRankCount(usize),
+    BadRankLength { rank: u8, files: usize },
+    UnknownPiece(char),
+    MissingKing(Color),
+    DuplicateKing(Color),
+    InvalidSideToMove,
+    InvalidCastling(char),
+    InvalidEnPassant,
+    InvalidMoveCounter,
+    IllegalPosition(PositionError),
+}
+
+// `field` is the zero-based index of the whitespace-separated FEN field at
+// fault: 0 is piece placement and 5 is the fullmove number.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct FenError {
+    pub field: usize,
+    pub kind: FenErrorKind,
+}
+
+impl FenError {
+    fn new(field: usize, kind: FenErrorKind) -> Self {
+        Self { field, kind }
+    }
+}
+
+impl fmt::Display for FenError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "field {}: ", self.field)?;
+        match self.kind {
+            FenErrorKind::MissingField => write!(f, "missing"),
+            FenErrorKind::TooManyFields => write!(f, "unexpected extra field"),
+            FenErrorKind::WrongRankCount(ranks) => write!(f, "expected 8 ranks, found {}", ranks),
+            FenErrorKind::BadRankLength { rank, files } => {
+                write!(f, "rank {} has {} files, expected 8", rank + 1, files)
+            }
+            FenErrorKind::UnknownPiece(c) => write!(f, "unknown piece '{}'", c),
+            FenErrorKind::MissingKing(color) => write!(f, "no {:?} king", color),
+            FenErrorKind::DuplicateKing(color) => write!(f, "more than one {:?} king", color),
+            FenErrorKind::InvalidSideToMove => write!(f, "side to move must be 'w' or 'b'"),
+            FenErrorKind::InvalidCastling(c) => write!(f, "invalid castling right '{}'", c),
+            FenErrorKind::InvalidEnPassant => write!(f, "invalid en-passant square"),
+            FenErrorKind::InvalidMoveCounter => write!(f, "invalid move counter"),
+            FenErrorKind::IllegalPosition(err) => write!(f, "illegal position: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for FenError {}
+
+impl From<PositionError> for FenError {
+    fn from(err: PositionError) -> Self {
+        let field = match err {
+            PositionError::OpponentInCheck => 1,
+            PositionError::InvalidCastlingRights => 2,
+            PositionError::InvalidEnPassant => 3,
+            _ => 0,
+        };
+        FenError::new(field, FenErrorKind::IllegalPosition(err))
+    }
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum PositionError {
+    WrongKingCount(Color),
+    KingSquareMismatch(Color),
+    PawnOnBackRank(Square),
+    TooManyPieces(Color),
+    OpponentInCheck,
+    InvalidCastlingRights,
+    InvalidEnPassant,
+}
+
+impl fmt::Display for PositionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            PositionError::WrongKingCount(color) => {
+                write!(f, "{:?} must have exactly one king", color)
+            }
+            PositionError::KingSquareMismatch(color) => {
+                write!(f, "{:?} king square does not match the board", color)
+            }
+            PositionError::PawnOnBackRank((file, rank)) => write!(
+                f,
+                "pawn on {}{}",
+                char::from(b'a' + file),
+                char::from(b'1' + rank)
+            ),
+            PositionError::TooManyPieces(color) => write!(f, "impossible {:?} piece counts", color),
+            PositionError::OpponentInCheck => write!(f, "side not to move is in check"),
+            PositionError::InvalidCastlingRights => {
+                write!(f, "castling rights without king and rook on home squares")
+            }
+            PositionError::InvalidEnPassant => {
+                write!(
+                    f,
+                    "en-passant square without a pawn that just double-pushed"
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for PositionError {}
+
+#[derive(Clone, Eq, PartialEq, Debug)]
 pub struct GameState {
     pub board: Board,
     pub side_to_move: Color,
@@ -27,11 +146,20 @@ pub struct GameState {
     pub en_passant: Option<Square>,
     pub white_king: Square,
     pub black_king: Square,
+    pub halfmove_clock: u32,
+    pub fullmove_number: u32,
+    pub zobrist: u64,
+}
+
+impl Default for GameState {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl GameState {
     pub fn new() -> Self {
-        let mut board = [[None; 8]; 8];
+        let mut board = Board::empty();
         let back_rank = [
             PieceKind::Rook,
             PieceKind::Knight,
@@ -43,127 +171,376 @@ impl GameState {
             PieceKind::Rook,
         ];
 
-        for (file, kind) in back_rank.iter().enumerate() {
-            board[0][file] = Some(Piece {
-                color: Color::White,
-                kind: *kind,
-            });
-            board[7][file] = Some(Piece {
-                color: Color::Black,
-                kind: *kind,
-            });
-        }
-
-        for file in 0..8 {
-            board[1][file] = Some(Piece {
-                color: Color::White,
-                kind: PieceKind::Pawn,
-            });
-            board[6][file] = Some(Piece {
-                color: Color::Black,
-                kind: PieceKind::Pawn,
-            });
+        for (file, kind) in (0..8).zip(back_rank) {
+            for (color, back, pawns) in [(Color::White, 0, 1), (Color::Black, 7, 6)] {
+                board.set_piece((file, back), Some(Piece { color, kind }));
+                board.set_piece(
+                    (file, pawns),
+                    Some(Piece {
+                        color,
+                        kind: PieceKind::Pawn,
+                    }),
+                );
+            }
         }
 
-        Self {
+        let mut state = Self {
             board,
             side_to_move: Color::White,
             castling: CastlingRights::new(),
             en_passant: None,
             white_king: (4, 0),
             black_king: (4, 7),
-        }
+            halfmove_clock: 0,
+            fullmove_number: 1,
+            zobrist: 0,
+        };
+        state.zobrist = zobrist::hash(&state);
+        state
     }
 
-    pub fn from_fen(fen: &str) -> Option<Self> {
+    pub fn from_fen(fen: &str) -> Result<Self, FenError> {
         let mut parts = fen.split_whitespace();
 
-        let board_str = parts.next()?;
-        let mut board: Board = [[None; 8]; 8];
-        let mut white_king = (0, 0);
-        let mut black_king = (0, 0);
+        let board_str = parts
+            .next()
+            .ok_or(FenError::new(0, FenErrorKind::MissingField))?;
+        let rank_count = board_str.split('/').count();
+        if rank_count != 8 {
+            return Err(FenError::new(0, FenErrorKind::WrongRankCount(rank_count)));
+        }
+
+        let mut board = Board::empty();
+        let mut white_king = None;
+        let mut black_king = None;
 
         for (rank_idx, rank_str) in board_str.split('/').enumerate() {
             let rank = 7 - rank_idx;
             let mut file = 0usize;
             for c in rank_str.chars() {
-                if let Some(skip) = c.to_digit(10) {
+                if let Some(skip @ 1..=8) = c.to_digit(10) {
                     file += skip as usize;
+                    continue;
+                }
+                let color = if c.is_ascii_uppercase() {
+                    Color::White
                 } else {
-                    let color = if c.is_uppercase() {
-                        Color::White
-                    } else {
-                        Color::Black
-                    };
-                    let kind = match c.to_ascii_lowercase() {
-                        'p' => PieceKind::Pawn,
-                        'n' => PieceKind::Knight,
-                        'b' => PieceKind::Bishop,
-                        'r' => PieceKind::Rook,
-                        'q' => PieceKind::Queen,
-                        'k' => {
-                            if color == Color::White {
-                                white_king = (file as u8, rank as u8);
-                            } else {
-                                black_king = (file as u8, rank as u8);
-                            }
-                            PieceKind::King
+                    Color::Black
+                };
+                let kind = match c.to_ascii_lowercase() {
+                    'p' => PieceKind::Pawn,
+                    'n' => PieceKind::Knight,
+                    'b' => PieceKind::Bishop,
+                    'r' => PieceKind::Rook,
+                    'q' => PieceKind::Queen,
+                    'k' => PieceKind::King,
+                    _ => return Err(FenError::new(0, FenErrorKind::UnknownPiece(c))),
+                };
+                if file < 8 {
+                    let sq = (file as u8, rank as u8);
+                    if kind == PieceKind::King {
+                        let king = match color {
+                            Color::White => &mut white_king,
+                            Color::Black => &mut black_king,
+                        };
+                        if king.replace(sq).is_some() {
+                            return Err(FenError::new(0, FenErrorKind::DuplicateKing(color)));
                         }
-                        _ => return None,
-                    };
-                    board[rank][file] = Some(Piece { color, kind });
-                    file += 1;
+                    }
+                    board.set_piece(sq, Some(Piece { color, kind }));
                 }
+                file += 1;
+            }
+            if file != 8 {
+                return Err(FenError::new(
+                    0,
+                    FenErrorKind::BadRankLength {
+                        rank: rank as u8,
+                        files: file,
+                    },
+                ));
             }
         }
 
-        let side_str = parts.next()?;
+        let white_king =
+            white_king.ok_or(FenError::new(0, FenErrorKind::MissingKing(Color::White)))?;
+        let black_king =
+            black_king.ok_or(FenError::new(0, FenErrorKind::MissingKing(Color::Black)))?;
+
+        let side_str = parts
+            .next()
+            .ok_or(FenError::new(1, FenErrorKind::MissingField))?;
         let side_to_move = match side_str {
             "w" => Color::White,
             "b" => Color::Black,
-            _ => return None,
+            _ => return Err(FenError::new(1, FenErrorKind::InvalidSideToMove)),
         };
 
         let castling_str = parts.next().unwrap_or("-");
-        let castling = CastlingRights {
-            white_kingside: castling_str.contains('K'),
-            white_queenside: castling_str.contains('Q'),
-            black_kingside: castling_str.contains('k'),
-            black_queenside: castling_str.contains('q'),
+        let mut castling = CastlingRights {
+            white_kingside: false,
+            white_queenside: false,
+            black_kingside: false,
+            black_queenside: false,
         };
+        if castling_str != "-" {
+            for c in castling_str.chars() {
+                let right = match c {
+                    'K' => &mut castling.white_kingside,
+                    'Q' => &mut castling.white_queenside,
+                    'k' => &mut castling.black_kingside,
+                    'q' => &mut castling.black_queenside,
+                    _ => return Err(FenError::new(2, FenErrorKind::InvalidCastling(c))),
+                };
+                if *right {
+                    return Err(FenError::new(2, FenErrorKind::InvalidCastling(c)));
+                }
+                *right = true;
+            }
+        }
 
         let ep_str = parts.next().unwrap_or("-");
         let en_passant = if ep_str == "-" {
             None
         } else {
-            let bytes = ep_str.as_bytes();
-            if bytes.len() == 2 {
-                let file = bytes[0].wrapping_sub(b'a');
-                let rank = bytes[1].wrapping_sub(b'1');
-                if file < 8 && rank < 8 {
-                    Some((file, rank))
-                } else {
-                    None
+            // The skipped square is on the sixth rank when white is to move
+            // and on the third when black is.
+            let ep_rank = match side_to_move {
+                Color::White => 5,
+                Color::Black => 2,
+            };
+            match ep_str.as_bytes() {
+                &[file @ b'a'..=b'h', rank] if rank == b'1' + ep_rank => {
+                    Some((file - b'a', ep_rank))
                 }
-            } else {
-                None
+                _ => return Err(FenError::new(3, FenErrorKind::InvalidEnPassant)),
             }
         };
 
-        Some(Self {
+        let halfmove_clock = match parts.next() {
+            Some(s) => s
+                .parse()
+                .map_err(|_| FenError::new(4, FenErrorKind::InvalidMoveCounter))?,
+            None => 0,
+        };
+        let fullmove_number = match parts.next() {
+            Some(s) => s
+                .parse()
+                .map_err(|_| FenError::new(5, FenErrorKind::InvalidMoveCounter))?,
+            None => 1,
+        };
+
+        if parts.next().is_some() {
+            return Err(FenError::new(6, FenErrorKind::TooManyFields));
+        }
+
+        let mut state = Self {
             board,
             side_to_move,
             castling,
             en_passant,
             white_king,
             black_king,
-        })
+            halfmove_clock,
+            fullmove_number,
+            zobrist: 0,
+        };
+        state.zobrist = zobrist::hash(&state);
+        Ok(state)
+    }
+
+    pub fn validate(&self) -> Result<(), PositionError> {
+        for color in [Color::White, Color::Black] {
+            self.validate_material(color)?;
+        }
+
+        for file in 0..8 {
+            for rank in [0, 7] {
+                if piece_at(&self.board, (file, rank)).is_some_and(|p| p.kind == PieceKind::Pawn) {
+                    return Err(PositionError::PawnOnBackRank((file, rank)));
+                }
+            }
+        }
+
+        let home_rights = [
+            (self.castling.white_kingside, Color::White, (7, 0)),
+            (self.castling.white_queenside, Color::White, (0, 0)),
+            (self.castling.black_kingside, Color::Black, (7, 7)),
+            (self.castling.black_queenside, Color::Black, (0, 7)),
+        ];
+        for (right, color, rook_sq) in home_rights {
+            let king_sq = match color {
+                Color::White => (4, 0),
+                Color::Black => (4, 7),
+            };
+            let king = Piece {
+                color,
+                kind: PieceKind::King,
+            };
+            let rook = Piece {
+                color,
+                kind: PieceKind::Rook,
+            };
+            if right
+                && (piece_at(&self.board, king_sq) != Some(king)
+                    || piece_at(&self.board, rook_sq) != Some(rook))
+            {
+                return Err(PositionError::InvalidCastlingRights);
+            }
+        }
+
+        if let Some((file, rank)) = self.en_passant {
+            // The pawn that just moved belongs to the side not to move and
+            // went from `origin` through the en-passant square to `pushed_to`.
+            let (ep_rank, origin, pushed_to) = match self.side_to_move {
+                Color::White => (5, 6, 4),
+                Color::Black => (2, 1, 3),
+            };
+            let pawn = Piece {
+                color: self.side_to_move.opposite(),
+                kind: PieceKind::Pawn,
+            };
+            if rank != ep_rank
+                || piece_at(&self.board, (file, rank)).is_some()
+                || piece_at(&self.board, (file, origin)).is_some()
+                || piece_at(&self.board, (file, pushed_to)) != Some(pawn)
+            {
+                return Err(PositionError::InvalidEnPassant);
+            }
+        }
+
+        if rules::is_in_check(self, self.side_to_move.opposite()) {
+            return Err(PositionError::OpponentInCheck);
+        }
+        Ok(())
+    }
+
+    fn validate_material(&self, color: Color) -> Result<(), PositionError> {
+        let mut counts = [0u32; 6];
+        let mut king_sq = None;
+        for rank in 0..8 {
+            for file in 0..8 {
+                let Some(piece) = piece_at(&self.board, (file, rank)) else {
+                    continue;
+                };
+                if piece.color != color {
+                    continue;
+                }
+                if piece.kind == PieceKind::King {
+                    king_sq = Some((file, rank));
+                }
+                counts[piece.kind as usize] += 1;
+            }
+        }
+
+        if counts[PieceKind::King as usize] != 1 {
+            return Err(PositionError::WrongKingCount(color));
+        }
+        let expected_king_sq = match color {
+            Color::White => self.white_king,
+            Color::Black => self.black_king,
+        };
+        if king_sq != Some(expected_king_sq) {
+            return Err(PositionError::KingSquareMismatch(color));
+        }
+
+        // Every piece beyond the starting set must have come from a promoted pawn.
+        let pawns = counts[PieceKind::Pawn as usize];
+        let promoted = counts[PieceKind::Knight as usize].saturating_sub(2)
+            + counts[PieceKind::Bishop as usize].saturating_sub(2)
+            + counts[PieceKind::Rook as usize].saturating_sub(2)
+            + counts[PieceKind::Queen as usize].saturating_sub(1);
+        if pawns > 8 || pawns + promoted > 8 {
+            return Err(PositionError::TooManyPieces(color));
+        }
+        Ok(())
+    }
+
+    pub fn to_fen(&self) -> String {
+        let mut fen = String::new();
+
+        for rank in (0..8).rev() {
+            let mut empty = 0;
+            for file in 0..8 {
+                match self.board.piece_at((file, rank)) {
+                    Some(piece) => {
+                        if empty > 0 {
+                            fen.push(char::from(b'0' + empty));
+                            empty = 0;
+                        }
+                        fen.push(piece_char(piece));
+                    }
+                    None => empty += 1,
+                }
+            }
+            if empty > 0 {
+                fen.push(char::from(b'0' + empty));
+            }
+            if rank > 0 {
+                fen.push('/');
+            }
+        }
+
+        fen.push(' ');
+        fen.push(match self.side_to_move {
+            Color::White => 'w',
+            Color::Black => 'b',
+        });
+
+        fen.push(' ');
+        let castling_start = fen.len();
+        if self.castling.white_kingside {
+            fen.push('K');
+        }
+        if self.castling.white_queenside {
+            fen.push('Q');
+        }
+        if self.castling.black_kingside {
+            fen.push('k');
+        }
+        if self.castling.black_queenside {
+            fen.push('q');
+        }
+        if fen.len() == castling_start {
+            fen.push('-');
+        }
+
+        fen.push(' ');
+        match self.en_passant {
+            Some((file, rank)) => {
+                fen.push(char::from(b'a' + file));
+                fen.push(char::from(b'1' + rank));
+            }
+            None => fen.push('-'),
+        }
+
+        fen.push_str(&format!(
+            " {} {}",
+            self.halfmove_clock, self.fullmove_number
+        ));
+        fen
+    }
+}
+
+fn piece_char(piece: Piece) -> char {
+    let c = match piece.kind {
+        PieceKind::Pawn => 'p',
+        PieceKind::Knight => 'n',
+        PieceKind::Bishop => 'b',
+        PieceKind::Rook => 'r',
+        PieceKind::Queen => 'q',
+        PieceKind::King => 'k',
+    };
+    match piece.color {
+        Color::White => c.to_ascii_uppercase(),
+        Color::Black => c,
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::rules;
 
     #[test]
     fn from_fen_starting_position() {
@@ -178,6 +555,37 @@ mod tests {
         assert!(state.castling.black_kingside);
         assert!(state.castling.black_queenside);
         assert!(state.en_passant.is_none());
+        assert_eq!(state.halfmove_clock, 0);
+        assert_eq!(state.fullmove_number, 1);
+    }
+
+    #[test]
+    fn from_fen_move_counters() {
+        let fen = "r3k2r/8/8/8/8/8/8/R3K2R b KQkq - 17 42";
+        let state = GameState::from_fen(fen).unwrap();
+
+        assert_eq!(state.halfmove_clock, 17);
+        assert_eq!(state.fullmove_number, 42);
+    }
+
+    #[test]
+    fn from_fen_missing_move_counters_default() {
+        let state = GameState::from_fen("4k3/8/8/8/8/8/8/4K3 w - -").unwrap();
+
+        assert_eq!(state.halfmove_clock, 0);
+        assert_eq!(state.fullmove_number, 1);
+    }
+
+    #[test]
+    fn from_fen_invalid_move_counters() {
+        assert_eq!(
+            GameState::from_fen("4k3/8/8/8/8/8/8/4K3 w - - x 1").unwrap_err(),
+            FenError::new(4, FenErrorKind::InvalidMoveCounter)
+        );
+        assert_eq!(
+            GameState::from_fen("4k3/8/8/8/8/8/8/4K3 w - - 0 -3").unwrap_err(),
+            FenError::new(5, FenErrorKind::InvalidMoveCounter)
+        );
     }
 
     #[test]
@@ -201,8 +609,253 @@ mod tests {
     }
 
     #[test]
-    fn from_fen_invalid_returns_none() {
-        assert!(GameState::from_fen("invalid").is_none());
-        assert!(GameState::from_fen("").is_none());
+    fn from_fen_invalid_returns_err() {
+        assert!(GameState::from_fen("invalid").is_err());
+        assert_eq!(
+            GameState::from_fen("").unwrap_err(),
+            FenError::new(0, FenErrorKind::MissingField)
+        );
+    }
+
+    fn fen_error(fen: &str) -> FenError {
+        GameState::from_fen(fen).unwrap_err()
+    }
+
+    #[test]
+    fn from_fen_rejects_bad_piece_placement() {
+        assert_eq!(
+            fen_error("4k3/8/8/8/8/8/4K3 w - - 0 1"),
+            FenError::new(0, FenErrorKind::WrongRankCount(7))
+        );
+        assert_eq!(
+            fen_error("4k3/8/8/8/8/8/8/4K2 w - - 0 1"),
+            FenError::new(0, FenErrorKind::BadRankLength { rank: 0, files: 7 })
+        );
+        assert_eq!(
+            fen_error("4k4/8/8/8/8/8/8/4K3 w - - 0 1"),
+            FenError::new(0, FenErrorKind::BadRankLength { rank: 7, files: 9 })
+        );
+        assert_eq!(
+            fen_error("4k3/8/8/8/3x4/8/8/4K3 w - - 0 1"),
+            FenError::new(0, FenErrorKind::UnknownPiece('x'))
+        );
+        assert_eq!(
+            fen_error("4k3/8/8/8/8/8/8/8 w - - 0 1"),
+            FenError::new(0, FenErrorKind::MissingKing(Color::White))
+        );
+        assert_eq!(
+            fen_error("4k3/8/8/8/8/8/8/3KK3 w - - 0 1"),
+            FenError::new(0, FenErrorKind::DuplicateKing(Color::White))
+        );
+    }
+
+    #[test]
+    fn from_fen_rejects_bad_fields() {
+        assert_eq!(
+            fen_error("4k3/8/8/8/8/8/8/4K3"),
+            FenError::new(1, FenErrorKind::MissingField)
+        );
+        assert_eq!(
+            fen_error("4k3/8/8/8/8/8/8/4K3 x - - 0 1"),
+            FenError::new(1, FenErrorKind::InvalidSideToMove)
+        );
+        assert_eq!(
+            fen_error("r3k2r/8/8/8/8/8/8/R3K2R w KQxq - 0 1"),
+            FenError::new(2, FenErrorKind::InvalidCastling('x'))
+        );
+        assert_eq!(
+            fen_error("r3k2r/8/8/8/8/8/8/R3K2R w KKq - 0 1"),
+            FenError::new(2, FenErrorKind::InvalidCastling('K'))
+        );
+        assert_eq!(
+            fen_error("4k3/8/8/3pP3/8/8/8/4K3 w - d3 0 1"),
+            FenError::new(3, FenErrorKind::InvalidEnPassant)
+        );
+        assert_eq!(
+            fen_error("4k3/8/8/8/8/8/8/4K3 w - z9 0 1"),
+            FenError::new(3, FenErrorKind::InvalidEnPassant)
+        );
+        assert_eq!(
+            fen_error("4k3/8/8/8/8/8/8/4K3 w - - 0 1 extra"),
+            FenError::new(6, FenErrorKind::TooManyFields)
+        );
+    }
+
+    #[test]
+    fn fen_error_display_names_field() {
+        assert_eq!(
+            fen_error("4k3/8/8/8/8/8/8/4K2 w - - 0 1").to_string(),
+            "field 0: rank 1 has 7 files, expected 8"
+        );
+    }
+
+    const FEN_CORPUS: [&str; 9] = [
+        "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+        "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1",
+        "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+        "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+        "8/8/4k3/8/2R5/8/5K2/8 b - - 37 71",
+        "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+        "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 0 1",
+        "r4rk1/1pp1qppp/p1np1n2/2b1p1B1/2B1P1b1/P1NP1N2/1PP1QPPP/R4RK1 w - - 0 1",
+        "4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1",
+    ];
+
+    #[test]
+    fn to_fen_starting_position() {
+        assert_eq!(
+            GameState::new().to_fen(),
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1"
+        );
+    }
+
+    #[test]
+    fn to_fen_no_castling_rights() {
+        let fen = "4k3/8/8/8/8/8/8/4K3 b - - 0 1";
+        assert_eq!(GameState::from_fen(fen).unwrap().to_fen(), fen);
+    }
+
+    #[test]
+    fn to_fen_round_trips_corpus() {
+        for fen in FEN_CORPUS {
+            let state = GameState::from_fen(fen).unwrap();
+            assert_eq!(state.to_fen(), fen);
+            assert_eq!(GameState::from_fen(&state.to_fen()).unwrap(), state);
+        }
+    }
+
+    #[test]
+    fn to_fen_round_trips_random_games() {
+        let mut seed: u64 = 0x9e37_79b9_7f4a_7c15;
+        let mut positions = 0;
+        for fen in FEN_CORPUS {
+            for _ in 0..8 {
+                let mut state = GameState::from_fen(fen).unwrap();
+                for _ in 0..80 {
+                    let moves = rules::legal_moves(&state);
+                    if moves.is_empty() {
+                        break;
+                    }
+                    seed ^= seed << 13;
+                    seed ^= seed >> 7;
+                    seed ^= seed << 17;
+                    let mv = moves.get((seed % moves.len() as u64) as usize).unwrap();
+                    rules::apply_move_unchecked(&mut state, mv);
+
+                    let round_tripped = GameState::from_fen(&state.to_fen()).unwrap();
+                    assert_eq!(round_tripped, state, "{}", state.to_fen());
+                    positions += 1;
+                }
+            }
+        }
+        assert!(positions > 1000);
+    }
+
+    fn position_error(fen: &str) -> PositionError {
+        GameState::from_fen(fen).unwrap().validate().unwrap_err()
+    }
+
+    #[test]
+    fn validate_accepts_legal_positions() {
+        for fen in FEN_CORPUS {
+            assert_eq!(
+                GameState::from_fen(fen).unwrap().validate(),
+                Ok(()),
+                "{}",
+                fen
+            );
+        }
+        assert_eq!(GameState::new().validate(), Ok(()));
+    }
+
+    #[test]
+    fn validate_rejects_pawns_on_back_ranks() {
+        assert_eq!(
+            position_error("4k3/8/8/8/8/8/8/P3K3 w - - 0 1"),
+            PositionError::PawnOnBackRank((0, 0))
+        );
+        assert_eq!(
+            position_error("4k2p/8/8/8/8/8/8/4K3 w - - 0 1"),
+            PositionError::PawnOnBackRank((7, 7))
+        );
+    }
+
+    #[test]
+    fn validate_rejects_side_not_to_move_in_check() {
+        assert_eq!(
+            position_error("4k3/8/8/8/8/8/8/4R1K1 w - - 0 1"),
+            PositionError::OpponentInCheck
+        );
+        assert_eq!(
+            position_error("R3k3/8/8/8/8/8/8/4K3 w - - 0 1"),
+            PositionError::OpponentInCheck
+        );
+    }
+
+    #[test]
+    fn validate_rejects_castling_rights_off_home_squares() {
+        assert_eq!(
+            position_error("r3k2r/8/8/8/8/8/8/R4K1R w K - 0 1"),
+            PositionError::InvalidCastlingRights
+        );
+        assert_eq!(
+            position_error("r3k2r/8/8/8/8/8/8/R3K1R1 w K - 0 1"),
+            PositionError::InvalidCastlingRights
+        );
+        assert_eq!(
+            position_error("1r2k2r/8/8/8/8/8/8/R3K2R w q - 0 1"),
+            PositionError::InvalidCastlingRights
+        );
+    }
+
+    #[test]
+    fn validate_rejects_en_passant_without_double_push() {
+        assert_eq!(
+            position_error("4k3/8/8/8/8/8/8/4K3 w - d6 0 1"),
+            PositionError::InvalidEnPassant
+        );
+        assert_eq!(
+            position_error("4k3/3p4/8/3p4/8/8/8/4K3 w - d6 0 1"),
+            PositionError::InvalidEnPassant
+        );
+        assert_eq!(
+            position_error("4k3/8/8/8/3P4/8/8/4K3 w - d6 0 1"),
+            PositionError::InvalidEnPassant
+        );
+    }
+
+    #[test]
+    fn validate_rejects_impossible_piece_counts() {
+        assert_eq!(
+            position_error("4k3/8/8/8/8/P7/PPPPPPPP/4K3 w - - 0 1"),
+            PositionError::TooManyPieces(Color::White)
+        );
+        assert_eq!(
+            position_error("3qk3/q7/8/8/8/8/pppppppp/4K3 w - - 0 1"),
+            PositionError::TooManyPieces(Color::Black)
+        );
+        assert!(
+            GameState::from_fen("QQQQk3/8/8/8/8/8/PPPP4/4K3 b - - 0 1")
+                .unwrap()
+                .validate()
+                .is_ok()
+        );
+    }
+
+    #[test]
+    fn validate_rejects_inconsistent_king_squares() {
+        let mut state = GameState::new();
+        state.white_king = (3, 0);
+        assert_eq!(
+            state.validate(),
+            Err(PositionError::KingSquareMismatch(Color::White))
+        );
+
+        let mut state = GameState::new();
+        state.board.set_piece((4, 7), None);
+        assert_eq!(
+            state.validate(),
+            Err(PositionError::WrongKingCount(Color::Black))
+        );
     }
 }
@@ -0,0 +1,84 @@
+use std::time::Duration;
+
+// Kept back from every allocation to cover GUI and network lag.
+pub const DEFAULT_MOVE_OVERHEAD: Duration = Duration::from_millis(30);
+
+// Moves we plan for when the GUI does not say how many remain until the
+// next time control.
+const DEFAULT_MOVES_TO_GO: u32 = 30;
+const MAX_MOVES_TO_GO: u32 = 50;
+
+// The soft limit is checked between iterations: past it, no new iteration
+// starts. The hard limit aborts the iteration in progress.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct TimeBudget {
+    pub soft: Duration,
+    pub hard: Duration,
+}
+
+impl TimeBudget {
+    pub fn for_clock(
+        remaining: Duration,
+        increment: Duration,
+        moves_to_go: Option<u32>,
+        overhead: Duration,
+    ) -> Self {
+        let available = remaining.saturating_sub(overhead);
+        let horizon = moves_to_go
+            .unwrap_or(DEFAULT_MOVES_TO_GO)
+            .clamp(1, MAX_MOVES_TO_GO);
+        let share = available / horizon + increment * 3 / 4;
+        // Never plan to spend more than three quarters of what is left on a
+        // single move, however generous the increment.
+        let hard = (share * 4).min(available * 3 / 4);
+        TimeBudget {
+            soft: share.min(hard),
+            hard,
+        }
+    }
+
+    pub fn for_move_time(move_time: Duration, overhead: Duration) -> Self {
+        let limit = move_time.saturating_sub(overhead);
+        TimeBudget {
+            soft: limit,
+            hard: limit,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(millis: u64) -> Duration {
+        Duration::from_millis(millis)
+    }
+
+    #[test]
+    fn clock_budget_splits_remaining_time() {
+        let budget = TimeBudget::for_clock(ms(60_030), ms(0), None, ms(30));
+        assert_eq!(budget.soft, ms(2_000));
+        assert_eq!(budget.hard, ms(8_000));
+
+        let budget = TimeBudget::for_clock(ms(10_030), ms(1_000), Some(10), ms(30));
+        assert_eq!(budget.soft, ms(1_750));
+        assert_eq!(budget.hard, ms(7_000));
+    }
+
+    #[test]
+    fn clock_budget_keeps_a_reserve() {
+        let budget = TimeBudget::for_clock(ms(1_030), ms(2_000), Some(1), ms(30));
+        assert_eq!(budget.hard, ms(750));
+        assert_eq!(budget.soft, ms(750));
+
+        let flagging = TimeBudget::for_clock(ms(20), ms(0), None, ms(30));
+        assert_eq!(flagging.hard, Duration::ZERO);
+    }
+
+    #[test]
+    fn move_time_budget_subtracts_overhead() {
+        let budget = TimeBudget::for_move_time(ms(500), ms(30));
+        assert_eq!(budget.soft, ms(470));
+        assert_eq!(budget.hard, ms(470));
+    }
+}
@@ -0,0 +1,283 @@
+use std::sync::atomic::{AtomicU8, AtomicU64, Ordering};
+
+use crate::moves::{Move, PackedMove};
+
+pub const DEFAULT_HASH_MB: usize = 16;
+pub const MAX_HASH_MB: usize = 4096;
+
+const ENTRIES_PER_BUCKET: usize = 4;
+const BUCKET_BYTES: usize = ENTRIES_PER_BUCKET * std::mem::size_of::<Entry>();
+// Generations wrap within the six bits an entry has for them.
+const GENERATION_MASK: u8 = 0x3f;
+// How many plies of depth one generation of age is worth when choosing
+// which entry to evict.
+const AGE_WEIGHT: i32 = 8;
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum Bound {
+    Exact,
+    // The score is at least this much: the search failed high.
+    Lower,
+    // The score is at most this much: the search failed low.
+    Upper,
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct TtEntry {
+    pub best_move: Option<Move>,
+    pub score: i32,
+    pub depth: u8,
+    pub bound: Bound,
+}
+
+// Shared between search threads without locks. Each slot keeps its key
+// XORed with its data, so a slot torn by two threads writing at once fails
+// the key check on the next probe instead of returning mixed data.
+pub struct TranspositionTable {
+    entries: Vec<Entry>,
+    buckets: usize,
+    generation: AtomicU8,
+}
+
+#[derive(Default)]
+struct Entry {
+    check: AtomicU64,
+    data: AtomicU64,
+}
+
+impl TranspositionTable {
+    pub fn new(megabytes: usize) -> Self {
+        let buckets = (megabytes.clamp(1, MAX_HASH_MB) << 20) / BUCKET_BYTES;
+        let mut entries = Vec::with_capacity(buckets * ENTRIES_PER_BUCKET);
+        entries.resize_with(buckets * ENTRIES_PER_BUCKET, Entry::default);
+        Self {
+            entries,
+            buckets,
+            generation: AtomicU8::new(0),
+        }
+    }
+
+    pub fn clear(&self) {
+        for entry in &self.entries {
+            entry.check.store(0, Ordering::Relaxed);
+            entry.data.store(0, Ordering::Relaxed);
+        }
+        self.generation.store(0, Ordering::Relaxed);
+    }
+
+    // Marks entries from earlier searches as stale, so they are the first
+    // to be replaced.
+    pub fn new_search(&self) {
+        let next = (self.generation() + 1) & GENERATION_MASK;
+        self.generation.store(next, Ordering::Relaxed);
+    }
+
+    pub fn probe(&self, key: u64) -> Option<TtEntry> {
+        self.bucket(key).iter().find_map(|entry| {
+            let data = entry.data.load(Ordering::Relaxed);
+            let check = entry.check.load(Ordering::Relaxed);
+            if check ^ data != key {
+                return None;
+            }
+            Data(data).unpack()
+        })
+    }
+
+    pub fn store(&self, key: u64, entry: TtEntry) {
+        let generation = self.generation();
+        let bucket = self.bucket(key);
+        let mut victim = &bucket[0];
+        let mut victim_worth = i32::MAX;
+        for slot in bucket {
+            let data = Data(slot.data.load(Ordering::Relaxed));
+            if slot.check.load(Ordering::Relaxed) ^ data.0 == key {
+                victim = slot;
+                break;
+            }
+            let worth = if data.is_empty() {
+                i32::MIN
+            } else {
+                let age = generation.wrapping_sub(data.generation()) & GENERATION_MASK;
+                data.depth() as i32 - AGE_WEIGHT * age as i32
+            };
+            if worth < victim_worth {
+                victim = slot;
+                victim_worth = worth;
+            }
+        }
+
+        // A search that found no best move should not erase the one an
+        // earlier search left for this position.
+        let mut entry = entry;
+        if entry.best_move.is_none() {
+            let old = Data(victim.data.load(Ordering::Relaxed));
+            if victim.check.load(Ordering::Relaxed) ^ old.0 == key {
+                entry.best_move = old.unpack().and_then(|old| old.best_move);
+            }
+        }
+        let data = Data::pack(entry, generation);
+        victim.check.store(key ^ data.0, Ordering::Relaxed);
+        victim.data.store(data.0, Ordering::Relaxed);
+    }
+
+    // Permille of a sample of slots holding entries from the current search,
+    // as UCI `hashfull` expects.
+    pub fn hashfull(&self) -> u16 {
+        let generation = self.generation();
+        let sample = &self.entries[..self.entries.len().min(1000)];
+        let used = sample
+            .iter()
+            .map(|entry| Data(entry.data.load(Ordering::Relaxed)))
+            .filter(|data| !data.is_empty() && data.generation() == generation)
+            .count();
+        (used * 1000 / sample.len()) as u16
+    }
+
+    fn generation(&self) -> u8 {
+        self.generation.load(Ordering::Relaxed)
+    }
+
+    fn bucket(&self, key: u64) -> &[Entry] {
+        let index = ((key as u128 * self.buckets as u128) >> 64) as usize;
+        let start = index * ENTRIES_PER_BUCKET;
+        &self.entries[start..start + ENTRIES_PER_BUCKET]
+    }
+}
+
+impl Default for TranspositionTable {
+    fn default() -> Self {
+        Self::new(DEFAULT_HASH_MB)
+    }
+}
+
+// Bits 0-15 hold the move (zero for none, since no move goes from a1 to a1),
+// 16-47 the score, 48-55 the depth, 56-57 the bound (zero for an empty slot)
+// and 58-63 the generation.
+#[derive(Copy, Clone)]
+struct Data(u64);
+
+impl Data {
+    fn pack(entry: TtEntry, generation: u8) -> Self {
+        let mv = entry
+            .best_move
+            .map_or(0, |mv| PackedMove::from(mv).raw() as u64);
+        let bound: u64 = match entry.bound {
+            Bound::Exact => 1,
+            Bound::Lower => 2,
+            Bound::Upper => 3,
+        };
+        Data(
+            mv | (entry.score as u32 as u64) << 16
+                | (entry.depth as u64) << 48
+                | bound << 56
+                | ((generation & GENERATION_MASK) as u64) << 58,
+        )
+    }
+
+    fn unpack(self) -> Option<TtEntry> {
+        let bound = match self.0 >> 56 & 0b11 {
+            1 => Bound::Exact,
+            2 => Bound::Lower,
+            3 => Bound::Upper,
+            _ => return None,
+        };
+        let raw = self.0 as u16;
+        Some(TtEntry {
+            best_move: PackedMove::from_raw(raw)
+                .filter(|_| raw != 0)
+                .map(Move::from),
+            score: (self.0 >> 16) as u32 as i32,
+            depth: self.depth(),
+            bound,
+        })
+    }
+
+    fn is_empty(self) -> bool {
+        self.0 >> 56 & 0b11 == 0
+    }
+
+    fn depth(self) -> u8 {
+        (self.0 >> 48) as u8
+    }
+
+    fn generation(self) -> u8 {
+        (self.0 >> 58) as u8 & GENERATION_MASK
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::PieceKind;
+    use crate::moves::MoveKind;
+
+    fn entry(depth: u8, score: i32) -> TtEntry {
+        TtEntry {
+            best_move: Some(Move {
+                from: (6, 6),
+                to: (6, 7),
+                kind: MoveKind::Promotion(PieceKind::Knight),
+            }),
+            score,
+            depth,
+            bound: Bound::Lower,
+        }
+    }
+
+    #[test]
+    fn stores_and_probes_entries() {
+        let table = TranspositionTable::new(1);
+        assert_eq!(table.probe(0x1234), None);
+
+        table.store(0x1234, entry(5, -42));
+        assert_eq!(table.probe(0x1234), Some(entry(5, -42)));
+        assert_eq!(table.probe(0x1235), None);
+
+        let no_move = TtEntry {
+            best_move: None,
+            ..entry(6, 17)
+        };
+        table.store(0x1234, no_move);
+        assert_eq!(table.probe(0x1234), Some(entry(6, 17)));
+
+        table.clear();
+        assert_eq!(table.probe(0x1234), None);
+    }
+
+    #[test]
+    fn replacement_prefers_shallow_and_stale_entries() {
+        let table = TranspositionTable::new(1);
+        // Keys with the same high bits share a bucket.
+        let keys = [1u64, 2, 3, 4, 5];
+        for (i, &key) in keys[..4].iter().enumerate() {
+            table.store(key, entry(10 + i as u8, 0));
+        }
+        table.store(keys[4], entry(1, 0));
+        assert_eq!(table.probe(keys[0]), None);
+        assert!(keys[1..].iter().all(|&key| table.probe(key).is_some()));
+
+        table.new_search();
+        table.new_search();
+        table.store(6, entry(1, 0));
+        assert_eq!(table.probe(keys[4]), None);
+
+        // Two searches old, a depth 11 entry is worth less than a fresh
+        // depth 1 one.
+        table.store(7, entry(1, 0));
+        assert_eq!(table.probe(keys[1]), None);
+        assert!(table.probe(6).is_some());
+        assert!(table.probe(keys[3]).is_some());
+    }
+
+    #[test]
+    fn hashfull_counts_current_generation() {
+        let table = TranspositionTable::new(1);
+        assert_eq!(table.hashfull(), 0);
+        for key in 0..20_000u64 {
+            table.store(key.wrapping_mul(0x9e37_79b9_7f4a_7c15), entry(1, 0));
+        }
+        assert!(table.hashfull() > 0);
+        table.new_search();
+        assert_eq!(table.hashfull(), 0);
+    }
+}
@@ -0,0 +1,147 @@
+use crate::board::{Color, Piece, PieceKind, Square, piece_at};
+use crate::state::{CastlingRights, GameState};
+
+struct Keys {
+    pieces: [[u64; 64]; 12],
+    side_to_move: u64,
+    castling: [u64; 4],
+    en_passant_file: [u64; 8],
+}
+
+const KEYS: Keys = generate_keys();
+
+const fn splitmix64(seed: &mut u64) -> u64 {
+    *seed = seed.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    let mut z = *seed;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+const fn generate_keys() -> Keys {
+    let mut seed = 0x5265_6a65_6374_4368;
+    let mut pieces = [[0; 64]; 12];
+    let mut piece = 0;
+    while piece < 12 {
+        let mut sq = 0;
+        while sq < 64 {
+            pieces[piece][sq] = splitmix64(&mut seed);
+            sq += 1;
+        }
+        piece += 1;
+    }
+    let side_to_move = splitmix64(&mut seed);
+    let mut castling = [0; 4];
+    let mut i = 0;
+    while i < 4 {
+        castling[i] = splitmix64(&mut seed);
+        i += 1;
+    }
+    let mut en_passant_file = [0; 8];
+    let mut i = 0;
+    while i < 8 {
+        en_passant_file[i] = splitmix64(&mut seed);
+        i += 1;
+    }
+    Keys {
+        pieces,
+        side_to_move,
+        castling,
+        en_passant_file,
+    }
+}
+
+pub fn piece_key(piece: Piece, sq: Square) -> u64 {
+    let kind = match piece.kind {
+        PieceKind::Pawn => 0,
+        PieceKind::Knight => 1,
+        PieceKind::Bishop => 2,
+        PieceKind::Rook => 3,
+        PieceKind::Queen => 4,
+        PieceKind::King => 5,
+    };
+    let color = match piece.color {
+        Color::White => 0,
+        Color::Black => 6,
+    };
+    KEYS.pieces[color + kind][sq.1 as usize * 8 + sq.0 as usize]
+}
+
+pub fn side_to_move_key() -> u64 {
+    KEYS.side_to_move
+}
+
+pub fn castling_key(castling: CastlingRights) -> u64 {
+    let mut key = 0;
+    if castling.white_kingside {
+        key ^= KEYS.castling[0];
+    }
+    if castling.white_queenside {
+        key ^= KEYS.castling[1];
+    }
+    if castling.black_kingside {
+        key ^= KEYS.castling[2];
+    }
+    if castling.black_queenside {
+        key ^= KEYS.castling[3];
+    }
+    key
+}
+
+pub fn en_passant_key(en_passant: Option<Square>) -> u64 {
+    match en_passant {
+        Some((file, _)) => KEYS.en_passant_file[file as usize],
+        None => 0,
+    }
+}
+
+pub fn hash(state: &GameState) -> u64 {
+    let mut key = 0;
+    for rank in 0..8 {
+        for file in 0..8 {
+            if let Some(piece) = piece_at(&state.board, (file, rank)) {
+                key ^= piece_key(piece, (file, rank));
+            }
+        }
+    }
+    if state.side_to_move == Color::Black {
+        key ^= side_to_move_key();
+    }
+    key ^ castling_key(state.castling) ^ en_passant_key(state.en_passant)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keys_are_distinct() {
+        let mut all: Vec<u64> = KEYS.pieces.iter().flatten().copied().collect();
+        all.push(KEYS.side_to_move);
+        all.extend(KEYS.castling);
+        all.extend(KEYS.en_passant_file);
+        let count = all.len();
+        all.sort_unstable();
+        all.dedup();
+        assert_eq!(all.len(), count);
+    }
+
+    #[test]
+    fn hash_depends_on_side_castling_and_en_passant() {
+        let base = GameState::from_fen("r3k2r/8/8/3pP3/8/8/8/R3K2R w KQkq - 0 1").unwrap();
+        let black = GameState::from_fen("r3k2r/8/8/3pP3/8/8/8/R3K2R b KQkq - 0 1").unwrap();
+        let no_castle = GameState::from_fen("r3k2r/8/8/3pP3/8/8/8/R3K2R w Kkq - 0 1").unwrap();
+        let ep = GameState::from_fen("r3k2r/8/8/3pP3/8/8/8/R3K2R w KQkq d6 0 1").unwrap();
+
+        assert_ne!(base.zobrist, black.zobrist);
+        assert_ne!(base.zobrist, no_castle.zobrist);
+        assert_ne!(base.zobrist, ep.zobrist);
+    }
+
+    #[test]
+    fn hash_ignores_move_counters() {
+        let a = GameState::from_fen("4k3/8/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+        let b = GameState::from_fen("4k3/8/8/8/8/8/8/4K3 w - - 12 40").unwrap();
+        assert_eq!(a.zobrist, b.zobrist);
+    }
+}
@@ -0,0 +1,62 @@
+use rejectchess::GameState;
+use rejectchess::rules::perft;
+
+// Positions and totals from the Chess Programming Wiki perft results page.
+const POSITIONS: [(&str, u8, u64); 6] = [
+    (
+        "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+        4,
+        197_281,
+    ),
+    (
+        "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+        3,
+        97_862,
+    ),
+    ("8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1", 4, 43_238),
+    (
+        "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+        3,
+        9_467,
+    ),
+    (
+        "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+        3,
+        62_379,
+    ),
+    (
+        "r4rk1/1pp1qppp/p1np1n2/2b1p1B1/2B1P1b1/P1NP1N2/1PP1QPPP/R4RK1 w - - 0 10",
+        3,
+        89_890,
+    ),
+];
+
+#[test]
+fn standard_positions_match_published_counts() {
+    for (fen, depth, expected) in POSITIONS {
+        let state = GameState::from_fen(fen).unwrap();
+        assert_eq!(perft(&state, depth), expected, "{fen} at depth {depth}");
+    }
+}
+
+#[test]
+fn mirrored_position_matches_original() {
+    let state =
+        GameState::from_fen("r2q1rk1/pP1p2pp/Q4n2/bbp1p3/Np6/1B3NBn/pPPP1PPP/R3K2R b KQ - 0 1")
+            .unwrap();
+    assert_eq!(perft(&state, 3), 9_467);
+}
+
+#[test]
+fn en_passant_and_promotion_edge_cases() {
+    for (fen, depth, expected) in [
+        ("8/8/8/8/k2Pp2Q/8/8/3K4 b - d3 0 1", 1, 6),
+        ("8/8/8/8/k1pP3R/8/8/3K4 b - d3 0 1", 1, 6),
+        ("8/8/8/2k5/3Pp3/8/8/4K3 b - d3 0 1", 1, 9),
+        ("n1n5/PPPk4/8/8/8/8/4Kppp/5N1N b - - 0 1", 3, 9_483),
+        ("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1", 2, 568),
+    ] {
+        let state = GameState::from_fen(fen).unwrap();
+        assert_eq!(perft(&state, depth), expected, "{fen} at depth {depth}");
+    }
+}
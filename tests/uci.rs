@@ -1,5 +1,6 @@
-use std::io::Write;
-use std::process::{Command, Stdio};
+use std::io::{BufRead, BufReader, Write};
+use std::process::{ChildStdin, Command, Stdio};
+use std::time::{Duration, Instant};
 
 #[test]
 fn go_outputs_info_before_bestmove() {
@@ -26,3 +27,290 @@ fn go_outputs_info_before_bestmove() {
         "info should come before bestmove"
     );
 }
+
+#[test]
+fn invalid_fen_is_reported() {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_rejectchess"))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+
+    child
+        .stdin
+        .as_mut()
+        .unwrap()
+        .write_all(b"position fen 4k3/8/8/8/8/8/8/4K2 w - - 0 1\nisready\nquit\n")
+        .unwrap();
+
+    let output = child.wait_with_output().unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+
+    assert!(stdout.contains("info string invalid fen: field 0: rank 1 has 7 files"));
+    assert!(stdout.contains("readyok"));
+}
+
+#[test]
+fn go_perft_reports_divide_and_total() {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_rejectchess"))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+
+    child
+        .stdin
+        .as_mut()
+        .unwrap()
+        .write_all(b"position startpos moves e2e4\ngo perft 2\ngo perft 0\ngo perft x\nquit\n")
+        .unwrap();
+
+    let output = child.wait_with_output().unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+
+    assert!(stdout.contains("e7e5: 29\n"));
+    let root_moves = stdout
+        .lines()
+        .filter(|line| line.contains(": ") && !line.starts_with("Nodes"))
+        .count();
+    assert_eq!(root_moves, 20);
+    assert!(stdout.contains("Nodes searched: 600\n"));
+    assert!(stdout.contains("Nodes searched: 1\n"));
+    assert!(stdout.contains("info string invalid perft depth"));
+    assert!(!stdout.contains("bestmove"));
+}
+
+// Sends `commands`, waits for the search they start to report its
+// bestmove, then quits. Returns everything the engine printed.
+fn search_until_bestmove(commands: &str) -> String {
+    let mut session = Session::start();
+    session.send(commands);
+    let output = session.read_until("bestmove ");
+    session.send("quit");
+    output
+}
+
+struct Session {
+    child: std::process::Child,
+    stdin: ChildStdin,
+    stdout: BufReader<std::process::ChildStdout>,
+}
+
+impl Session {
+    fn start() -> Self {
+        let mut child = Command::new(env!("CARGO_BIN_EXE_rejectchess"))
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .unwrap();
+        let stdin = child.stdin.take().unwrap();
+        let stdout = BufReader::new(child.stdout.take().unwrap());
+        Session {
+            child,
+            stdin,
+            stdout,
+        }
+    }
+
+    fn send(&mut self, commands: &str) {
+        writeln!(self.stdin, "{}", commands).unwrap();
+        self.stdin.flush().unwrap();
+    }
+
+    // Everything printed up to and including the first line starting with
+    // `prefix`.
+    fn read_until(&mut self, prefix: &str) -> String {
+        let mut output = String::new();
+        loop {
+            let mut line = String::new();
+            assert!(
+                self.stdout.read_line(&mut line).unwrap() > 0,
+                "engine exited before {prefix:?}: {output}"
+            );
+            output.push_str(&line);
+            if line.starts_with(prefix) {
+                return output;
+            }
+        }
+    }
+}
+
+impl Drop for Session {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+#[test]
+fn go_depth_reports_every_iteration() {
+    let stdout = search_until_bestmove("position startpos\ngo depth 3");
+
+    let depths: Vec<&str> = stdout
+        .lines()
+        .filter_map(|line| line.strip_prefix("info depth "))
+        .map(|rest| rest.split_whitespace().next().unwrap())
+        .collect();
+    assert_eq!(depths, vec!["1", "2", "3"]);
+    assert!(stdout.lines().last().unwrap().starts_with("bestmove "));
+}
+
+#[test]
+fn go_movetime_returns_within_the_limit() {
+    let start = Instant::now();
+    let stdout = search_until_bestmove("position startpos\ngo movetime 300");
+
+    assert!(stdout.contains("bestmove "));
+    assert!(start.elapsed() >= Duration::from_millis(200));
+    assert!(start.elapsed() < Duration::from_secs(2));
+}
+
+#[test]
+fn go_mate_reports_mate_score() {
+    let stdout = search_until_bestmove(
+        "position fen r1bqkb1r/pppp1ppp/2n2n2/4p2Q/2B1P3/8/PPPP1PPP/RNB1K1NR w KQkq - 4 4\n\
+         go mate 1",
+    );
+
+    assert!(stdout.contains("score mate 1 "));
+    assert!(stdout.contains("bestmove h5f7"));
+}
+
+#[test]
+fn go_nodes_stops_searching() {
+    let stdout = search_until_bestmove("position startpos\ngo nodes 20000");
+
+    // The engine reads its node counter every 1024 nodes.
+    let nodes = stdout
+        .lines()
+        .rev()
+        .find(|line| line.starts_with("info depth "))
+        .and_then(|line| line.split(" nodes ").nth(1))
+        .and_then(|rest| rest.split_whitespace().next())
+        .and_then(|value| value.parse::<u64>().ok())
+        .expect("an info line with nodes");
+    assert!(nodes <= 20_000 + 1024, "searched {} nodes", nodes);
+    assert!(stdout.contains("bestmove "));
+}
+
+#[test]
+fn malformed_go_parameters_are_reported() {
+    let stdout =
+        search_until_bestmove("position startpos\ngo wtime soon depth 2 sideways movestogo");
+
+    assert!(stdout.contains("info string invalid wtime value: soon"));
+    assert!(stdout.contains("info string unknown go parameter: sideways"));
+    assert!(stdout.contains("info string missing movestogo value"));
+    assert!(stdout.contains("info depth 2 "));
+    assert!(!stdout.contains("info depth 3 "));
+}
+
+#[test]
+fn isready_and_stop_are_answered_during_infinite_search() {
+    let mut session = Session::start();
+    session.send("position startpos\ngo infinite");
+    session.read_until("info depth 1 ");
+
+    let start = Instant::now();
+    session.send("isready");
+    let before_ready = session.read_until("readyok");
+    assert!(!before_ready.contains("bestmove"));
+
+    session.send("stop");
+    let after_stop = session.read_until("bestmove ");
+    assert!(start.elapsed() < Duration::from_secs(2));
+    assert!(!after_stop.contains("bestmove 0000"));
+    session.send("quit");
+}
+
+#[test]
+fn ponder_search_waits_for_ponderhit() {
+    let mut session = Session::start();
+    session.send("position startpos\ngo ponder depth 2 movetime 100");
+    session.read_until("info depth 2 ");
+
+    std::thread::sleep(Duration::from_millis(300));
+    session.send("isready");
+    let waiting = session.read_until("readyok");
+    assert!(!waiting.contains("bestmove"));
+
+    session.send("ponderhit");
+    session.read_until("bestmove ");
+    session.send("quit");
+}
+
+#[test]
+fn hash_options_are_advertised_and_applied() {
+    let mut session = Session::start();
+    session.send("uci");
+    let stdout = session.read_until("uciok");
+    assert!(stdout.contains("option name Hash type spin default 16 min 1 max 4096"));
+    assert!(stdout.contains("option name Clear Hash type button"));
+
+    session.send(
+        "setoption name Hash value 0\nsetoption name Hash\nsetoption name Threads value 2\n\
+         setoption name Hash value 2\nsetoption name Clear Hash\nucinewgame\n\
+         position startpos\ngo depth 4",
+    );
+    let stdout = session.read_until("bestmove");
+    assert!(stdout.contains("info string invalid Hash value: 0"));
+    assert!(stdout.contains("info string missing Hash value"));
+    assert!(stdout.contains("info string unknown option: Threads"));
+    let hashfull = stdout
+        .lines()
+        .find(|line| line.starts_with("info depth 4 "))
+        .and_then(|line| line.split(" hashfull ").nth(1))
+        .and_then(|rest| rest.split_whitespace().next())
+        .and_then(|value| value.parse::<u16>().ok())
+        .expect("depth 4 reports hashfull");
+    assert!(hashfull > 0 && hashfull <= 1000);
+}
+
+#[test]
+fn position_moves_apply_past_a_decided_game() {
+    let stdout = search_until_bestmove(
+        "position fen 8/3k4/8/p1p1p1p1/P1P1P1P1/8/3K4/8 w - - 0 1 moves d2e2\ngo depth 2",
+    );
+    assert!(!stdout.contains("info string"));
+    assert!(stdout.contains("bestmove d7"), "{}", stdout);
+
+    let shuffle = "g1f3 g8f6 f3g1 f6g8 ".repeat(4);
+    let stdout = search_until_bestmove(&format!(
+        "position startpos moves {}e2e4\ngo depth 2",
+        shuffle
+    ));
+    let mv = bestmove(&stdout);
+    assert!(matches!(&mv[1..2], "7" | "8"), "{}", mv);
+}
+
+#[test]
+fn illegal_position_moves_are_reported() {
+    let stdout = search_until_bestmove("position startpos moves e2e4 e7e4 d7d5\ngo depth 1");
+    assert!(stdout.contains("info string illegal move: e7e4"));
+    // Only e2e4 was played, so it is Black's move.
+    let mv = bestmove(&stdout);
+    assert!(matches!(&mv[1..2], "7" | "8"), "{}", mv);
+}
+
+fn bestmove(stdout: &str) -> &str {
+    stdout
+        .lines()
+        .find_map(|line| line.strip_prefix("bestmove "))
+        .and_then(|rest| rest.split_whitespace().next())
+        .expect("a bestmove line")
+}
+
+#[test]
+fn infinite_search_without_moves_waits_for_stop() {
+    let mut session = Session::start();
+    session.send("position fen R6k/6pp/8/8/8/8/8/K7 b - - 0 1\ngo infinite");
+    // Give a search that wrongly ends at once time to say so.
+    std::thread::sleep(Duration::from_millis(200));
+    session.send("isready");
+    let before_ready = session.read_until("readyok");
+    assert!(!before_ready.contains("bestmove"));
+
+    session.send("stop");
+    session.read_until("bestmove 0000");
+    session.send("quit");
+}